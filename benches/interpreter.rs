@@ -0,0 +1,34 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+// A few thousand arithmetic statements, mixing declarations and updates
+fn program() -> String {
+    let mut source = String::from("عرف مجموع = ٠.\n");
+    for i in 0..2000 {
+        source.push_str(&format!("عرف س{} = {} * ٣ + ٤ / ٢ - ١.\n", i, i));
+        source.push_str(&format!("مجموع = مجموع + س{}.\n", i));
+    }
+    source
+}
+
+fn lexer(c: &mut Criterion) {
+    let source = program();
+    let mut group = c.benchmark_group("lexer");
+    group.throughput(Throughput::Bytes(source.len() as u64));
+    group.bench_function("tokenize", |b| {
+        b.iter(|| amoud::tokenize(black_box(&source)).unwrap())
+    });
+    group.finish();
+}
+
+fn end_to_end(c: &mut Criterion) {
+    let source = program();
+    let mut group = c.benchmark_group("end_to_end");
+    group.throughput(Throughput::Bytes(source.len() as u64));
+    group.bench_function("run_source", |b| {
+        b.iter(|| amoud::run_source(black_box(&source)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, lexer, end_to_end);
+criterion_main!(benches);
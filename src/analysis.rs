@@ -0,0 +1,69 @@
+use crate::parser::ASTNode;
+
+// Finds statements that can never run because they follow an `أرجع` in the same block
+pub fn check(ast: &[ASTNode]) -> Vec<String> {
+    let mut warnings = Vec::new();
+    check_block(ast, &mut warnings);
+    warnings
+}
+
+fn check_block(statements: &[ASTNode], warnings: &mut Vec<String>) {
+    for (i, statement) in statements.iter().enumerate() {
+        if let ASTNode::Return { span, .. } = statement {
+            if i + 1 < statements.len() {
+                warnings.push(format!("Unreachable code after أرجع at {}", span));
+            }
+        }
+        check_node(statement, warnings);
+    }
+}
+
+fn check_node(node: &ASTNode, warnings: &mut Vec<String>) {
+    match node {
+        ASTNode::IfStatement {
+            then_branch,
+            else_branch,
+            ..
+        } => {
+            check_block(then_branch, warnings);
+            if let Some(else_branch) = else_branch {
+                check_block(else_branch, warnings);
+            }
+        }
+        ASTNode::WhileLoop { body, .. }
+        | ASTNode::ForEach { body, .. }
+        | ASTNode::Repeat { body, .. }
+        | ASTNode::FunctionDeclaration { body, .. } => check_block(body, warnings),
+        // An if may also appear as the value being declared or assigned
+        ASTNode::VariableDeclaration { value, .. }
+        | ASTNode::ConstantDeclaration { value, .. }
+        | ASTNode::Assignment { value, .. } => check_node(value, warnings),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn warnings(source: &str) -> Vec<String> {
+        check(&crate::parse_source(source).unwrap())
+    }
+
+    #[test]
+    fn code_after_a_return_is_reported() {
+        assert_eq!(
+            warnings("دالة د() ف\n  أرجع ١.\n  اطبع ٢.\nتم"),
+            ["Unreachable code after أرجع at line 2, col 3"]
+        );
+        assert_eq!(
+            warnings("دالة د(س) ف\n  لو س ف\n    أرجع ١.\n    س = ٢.\n  تم\nتم"),
+            ["Unreachable code after أرجع at line 3, col 5"]
+        );
+    }
+
+    #[test]
+    fn return_at_the_end_of_a_block_is_fine() {
+        assert!(warnings("دالة د(س) ف\n  لو س ف\n    أرجع ١.\n  تم\n  أرجع ٢.\nتم").is_empty());
+    }
+}
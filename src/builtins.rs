@@ -0,0 +1,746 @@
+use crate::interpreter::{Builtin, Interpreter, MapKey, Value};
+use crate::lexer::{self, SpannedToken, Token};
+
+pub(crate) fn all() -> Vec<(&'static str, Builtin)> {
+    vec![
+        ("جذر", square_root),
+        ("مطلق", absolute),
+        ("تقريب", round),
+        ("أرضية", floor),
+        ("سقف", ceil),
+        ("الأدنى", min),
+        ("الأقصى", max),
+        ("قسمة_صحيحة", integer_divide),
+        ("اقرأ", read),
+        ("عدد", parse_number),
+        ("صحيح", to_integer),
+        ("عشري", to_float),
+        ("نص", to_string),
+        ("نوع", type_of),
+        ("طول", length),
+        ("يحتوي", contains),
+        ("عكس", reverse),
+        ("رتب", sort),
+        ("مدى", range),
+        ("قص", substring),
+        ("احذف_مسافات", trim),
+        ("طبع_موحد", normalize),
+        ("حقل", field),
+        ("احذف", remove),
+        ("كله", all_true),
+        ("أي", any_true),
+        ("خطأ", raise_error),
+    ]
+}
+
+fn expect_arity(name: &str, args: &[Value], expected: usize) -> Result<(), String> {
+    if args.len() != expected {
+        return Err(format!(
+            "Function {} expects {} arguments, got {}",
+            name,
+            expected,
+            args.len()
+        ));
+    }
+    Ok(())
+}
+
+fn expect_number(name: &str, value: &Value) -> Result<f64, String> {
+    match value {
+        Value::Integer(n) => Ok(*n as f64),
+        Value::Number(n) => Ok(*n),
+        _ => Err(format!(
+            "Function {} expects a number, got {:?}",
+            name, value
+        )),
+    }
+}
+
+fn expect_string<'a>(name: &str, value: &'a Value) -> Result<&'a str, String> {
+    match value {
+        Value::String(s) => Ok(s),
+        value => Err(format!(
+            "Function {} expects a string, got {:?}",
+            name, value
+        )),
+    }
+}
+
+fn expect_booleans(name: &str, value: &Value) -> Result<Vec<bool>, String> {
+    let elements = match value {
+        Value::Array(elements) => elements,
+        value => {
+            return Err(format!(
+                "Function {} expects an array, got {:?}",
+                name, value
+            ))
+        }
+    };
+    elements
+        .iter()
+        .map(|element| match element {
+            Value::Boolean(b) => Ok(*b),
+            element => Err(format!(
+                "Function {} expects boolean elements, got {:?}",
+                name, element
+            )),
+        })
+        .collect()
+}
+
+// Unlike `as i64`, which saturates and turns NaN into 0, fails for floats no integer can hold
+fn float_to_integer(name: &str, n: f64) -> Result<Value, String> {
+    // -2^63 is exactly representable, while i64::MAX rounds up to 2^63
+    if n.is_finite() && n >= i64::MIN as f64 && n < -(i64::MIN as f64) {
+        Ok(Value::Integer(n as i64))
+    } else {
+        Err(format!(
+            "Function {} cannot convert {} to an integer",
+            name, n
+        ))
+    }
+}
+
+fn square_root(_: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    expect_arity("جذر", &args, 1)?;
+    let n = expect_number("جذر", &args[0])?;
+    if n < 0.0 {
+        return Err("Cannot take the square root of a negative number".to_string());
+    }
+    Ok(Value::Number(n.sqrt()))
+}
+
+fn absolute(_: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    expect_arity("مطلق", &args, 1)?;
+    match args[0] {
+        Value::Integer(n) => n
+            .checked_abs()
+            .map(Value::Integer)
+            .ok_or_else(|| "Integer overflow".to_string()),
+        ref value => Ok(Value::Number(expect_number("مطلق", value)?.abs())),
+    }
+}
+
+fn round(_: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    expect_arity("تقريب", &args, 1)?;
+    match args[0] {
+        Value::Integer(n) => Ok(Value::Integer(n)),
+        ref value => float_to_integer("تقريب", expect_number("تقريب", value)?.round()),
+    }
+}
+
+fn floor(_: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    expect_arity("أرضية", &args, 1)?;
+    match args[0] {
+        Value::Integer(n) => Ok(Value::Integer(n)),
+        ref value => float_to_integer("أرضية", expect_number("أرضية", value)?.floor()),
+    }
+}
+
+fn ceil(_: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    expect_arity("سقف", &args, 1)?;
+    match args[0] {
+        Value::Integer(n) => Ok(Value::Integer(n)),
+        ref value => float_to_integer("سقف", expect_number("سقف", value)?.ceil()),
+    }
+}
+
+fn min(_: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    expect_arity("الأدنى", &args, 2)?;
+    match (&args[0], &args[1]) {
+        (Value::Integer(a), Value::Integer(b)) => Ok(Value::Integer(*a.min(b))),
+        (a, b) => Ok(Value::Number(
+            expect_number("الأدنى", a)?.min(expect_number("الأدنى", b)?),
+        )),
+    }
+}
+
+fn max(_: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    expect_arity("الأقصى", &args, 2)?;
+    match (&args[0], &args[1]) {
+        (Value::Integer(a), Value::Integer(b)) => Ok(Value::Integer(*a.max(b))),
+        (a, b) => Ok(Value::Number(
+            expect_number("الأقصى", a)?.max(expect_number("الأقصى", b)?),
+        )),
+    }
+}
+
+fn integer_divide(_: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    expect_arity("قسمة_صحيحة", &args, 2)?;
+    let (dividend, divisor) = match (&args[0], &args[1]) {
+        (Value::Integer(a), Value::Integer(b)) => (*a, *b),
+        (a, b) => {
+            return Err(format!(
+                "Function قسمة_صحيحة expects two integers, got {:?} and {:?}",
+                a, b
+            ))
+        }
+    };
+    if divisor == 0 {
+        return Err("Division by zero".to_string());
+    }
+    dividend
+        .checked_div(divisor)
+        .map(Value::Integer)
+        .ok_or_else(|| "Integer overflow".to_string())
+}
+
+fn read(interpreter: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    let prompt = match args.as_slice() {
+        [] => None,
+        [Value::String(prompt)] => Some(prompt.as_str()),
+        _ => return Err("Function اقرأ expects an optional string prompt".to_string()),
+    };
+    Ok(Value::String(interpreter.read_line(prompt)?))
+}
+
+fn parse_number(_: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    expect_arity("عدد", &args, 1)?;
+    let text = match &args[0] {
+        Value::String(text) => text,
+        value => return Err(format!("Function عدد expects a string, got {:?}", value)),
+    };
+    let invalid = || format!("Cannot convert \"{}\" to a number", text);
+    let tokens = lexer::run_literal(text.trim()).map_err(|_| invalid())?;
+    let tokens: Vec<&Token> = tokens
+        .iter()
+        .map(|SpannedToken { token, .. }| token)
+        .collect();
+    match tokens.as_slice() {
+        [Token::Integer(n)] => Ok(Value::Integer(*n)),
+        [Token::Number(n)] => Ok(Value::Number(*n)),
+        [Token::Minus, Token::Integer(n)] => Ok(Value::Integer(-n)),
+        [Token::Minus, Token::Number(n)] => Ok(Value::Number(-n)),
+        _ => Err(invalid()),
+    }
+}
+
+fn to_integer(_: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    expect_arity("صحيح", &args, 1)?;
+    match args[0] {
+        Value::Integer(n) => Ok(Value::Integer(n)),
+        ref value => float_to_integer("صحيح", expect_number("صحيح", value)?.trunc()),
+    }
+}
+
+fn to_float(_: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    expect_arity("عشري", &args, 1)?;
+    Ok(Value::Number(expect_number("عشري", &args[0])?))
+}
+
+fn to_string(_: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    expect_arity("نص", &args, 1)?;
+    Ok(Value::String(args[0].to_string()))
+}
+
+fn type_of(_: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    expect_arity("نوع", &args, 1)?;
+    let name = match args[0] {
+        Value::Integer(_) | Value::Number(_) => "عدد",
+        Value::String(_) => "نص",
+        Value::Boolean(_) => "منطقي",
+        Value::Array(_) => "مصفوفة",
+        Value::Record(_) => "صف",
+        Value::Map(_) => "خريطة",
+        Value::Function(_) => "دالة",
+        Value::Null => "فارغ",
+    };
+    Ok(Value::String(name.to_string()))
+}
+
+fn length(_: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    expect_arity("طول", &args, 1)?;
+    let length = match &args[0] {
+        Value::String(s) => s.chars().count(),
+        Value::Array(elements) => elements.len(),
+        Value::Map(entries) => entries.len(),
+        value => {
+            return Err(format!(
+                "Function طول expects a string, array or map, got {:?}",
+                value
+            ))
+        }
+    };
+    Ok(Value::Integer(length as i64))
+}
+
+fn contains(_: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    expect_arity("يحتوي", &args, 2)?;
+    match (&args[0], &args[1]) {
+        (Value::String(haystack), Value::String(needle)) => {
+            Ok(Value::Boolean(haystack.contains(needle.as_str())))
+        }
+        (Value::Array(elements), needle) => Ok(Value::Boolean(elements.contains(needle))),
+        (haystack, needle) => Err(format!(
+            "Function يحتوي cannot search for {:?} in {:?}",
+            needle, haystack
+        )),
+    }
+}
+
+fn reverse(_: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    expect_arity("عكس", &args, 1)?;
+    match &args[0] {
+        Value::Array(elements) => Ok(Value::Array(elements.iter().rev().cloned().collect())),
+        value => Err(format!("Function عكس expects an array, got {:?}", value)),
+    }
+}
+
+fn sort(_: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    expect_arity("رتب", &args, 1)?;
+    let elements = match &args[0] {
+        Value::Array(elements) => elements,
+        value => return Err(format!("Function رتب expects an array, got {:?}", value)),
+    };
+    let numbers: Option<Vec<(f64, Value)>> = elements
+        .iter()
+        .map(|e| expect_number("رتب", e).ok().map(|n| (n, e.clone())))
+        .collect();
+    if let Some(mut numbers) = numbers {
+        numbers.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+        return Ok(Value::Array(numbers.into_iter().map(|(_, e)| e).collect()));
+    }
+    let strings: Option<Vec<String>> = elements
+        .iter()
+        .map(|e| match e {
+            Value::String(s) => Some(s.clone()),
+            _ => None,
+        })
+        .collect();
+    if let Some(mut strings) = strings {
+        strings.sort();
+        return Ok(Value::Array(
+            strings.into_iter().map(Value::String).collect(),
+        ));
+    }
+    Err("Function رتب expects an array of only numbers or only strings".to_string())
+}
+
+// The integers from start up to but not including end
+fn range(_: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    expect_arity("مدى", &args, 2)?;
+    let (start, end) = match (&args[0], &args[1]) {
+        (Value::Integer(start), Value::Integer(end)) => (*start, *end),
+        _ => return Err("Function مدى expects two integers".to_string()),
+    };
+    if start > end {
+        return Err(format!(
+            "Invalid range {}..{}: start is after end",
+            start, end
+        ));
+    }
+    let too_long = || format!("Range {}..{} is too long", start, end);
+    let length = end
+        .checked_sub(start)
+        .and_then(|length| usize::try_from(length).ok())
+        .ok_or_else(too_long)?;
+    let mut values = Vec::new();
+    values.try_reserve_exact(length).map_err(|_| too_long())?;
+    values.extend((start..end).map(Value::Integer));
+    Ok(Value::Array(values))
+}
+
+fn substring(_: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    expect_arity("قص", &args, 3)?;
+    let (text, start, end) = match (&args[0], &args[1], &args[2]) {
+        (Value::String(text), Value::Integer(start), Value::Integer(end)) => (text, *start, *end),
+        _ => return Err("Function قص expects a string and two integer indices".to_string()),
+    };
+    let length = text.chars().count() as i64;
+    if start < 0 || start > end || end > length {
+        return Err(format!(
+            "Invalid range {}..{} for string of length {}",
+            start, end, length
+        ));
+    }
+    Ok(Value::String(
+        text.chars()
+            .skip(start as usize)
+            .take((end - start) as usize)
+            .collect(),
+    ))
+}
+
+fn all_true(_: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    expect_arity("كله", &args, 1)?;
+    let values = expect_booleans("كله", &args[0])?;
+    Ok(Value::Boolean(values.into_iter().all(|b| b)))
+}
+
+fn any_true(_: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    expect_arity("أي", &args, 1)?;
+    let values = expect_booleans("أي", &args[0])?;
+    Ok(Value::Boolean(values.into_iter().any(|b| b)))
+}
+
+fn trim(_: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    expect_arity("احذف_مسافات", &args, 1)?;
+    let text = expect_string("احذف_مسافات", &args[0])?;
+    Ok(Value::String(text.trim().to_string()))
+}
+
+// Unifies the alef forms أ, إ and آ into ا so spelling variants compare equal
+fn normalize(_: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    expect_arity("طبع_موحد", &args, 1)?;
+    let text = expect_string("طبع_موحد", &args[0])?;
+    Ok(Value::String(
+        text.chars()
+            .map(|c| match c {
+                'أ' | 'إ' | 'آ' => 'ا',
+                c => c,
+            })
+            .collect(),
+    ))
+}
+
+fn field(_: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    expect_arity("حقل", &args, 2)?;
+    match (&args[0], &args[1]) {
+        (Value::Record(fields), Value::String(name)) => fields
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("Record has no field named {}", name)),
+        _ => Err("Function حقل expects a record and a field name".to_string()),
+    }
+}
+
+fn remove(_: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    expect_arity("احذف", &args, 2)?;
+    let mut args = args.into_iter();
+    match (args.next(), args.next()) {
+        (Some(Value::Map(mut entries)), Some(key)) => {
+            let key = MapKey::try_from(key)?;
+            if entries.shift_remove(&key).is_none() {
+                return Err(format!("Map has no key {}", key));
+            }
+            Ok(Value::Map(entries))
+        }
+        _ => Err("Function احذف expects a map and a key".to_string()),
+    }
+}
+
+fn raise_error(_: &mut Interpreter, args: Vec<Value>) -> Result<Value, String> {
+    expect_arity("خطأ", &args, 1)?;
+    Err(args[0].to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn eval(source: &str) -> Result<Value, String> {
+        Interpreter::new().eval(source)
+    }
+
+    #[test]
+    fn read_returns_a_line_from_the_injected_input() {
+        let mut interpreter = Interpreter::new().with_input(Cursor::new("مرحبا\nعالم\n"));
+        assert_eq!(
+            interpreter.eval("اقرأ()."),
+            Ok(Value::String("مرحبا".to_string()))
+        );
+        assert_eq!(
+            interpreter.eval("اقرأ()."),
+            Ok(Value::String("عالم".to_string()))
+        );
+    }
+
+    #[test]
+    fn range_counts_up_to_the_end() {
+        assert_eq!(
+            eval("مدى(١، ٤)."),
+            Ok(Value::Array(vec![
+                Value::Integer(1),
+                Value::Integer(2),
+                Value::Integer(3)
+            ]))
+        );
+        assert_eq!(eval("مدى(٢، ٢)."), Ok(Value::Array(vec![])));
+    }
+
+    #[test]
+    fn range_rejects_invalid_bounds() {
+        assert_eq!(
+            eval("مدى(٤، ١)."),
+            Err("Invalid range 4..1: start is after end at line 1, col 1".to_string())
+        );
+        assert_eq!(
+            eval("مدى(١، ٢٫٥)."),
+            Err("Function مدى expects two integers at line 1, col 1".to_string())
+        );
+        assert_eq!(
+            eval("مدى(0، 9223372036854775807)."),
+            Err("Range 0..9223372036854775807 is too long at line 1, col 1".to_string())
+        );
+    }
+
+    #[test]
+    fn math_builtins() {
+        assert_eq!(eval("جذر(٩)."), Ok(Value::Number(3.0)));
+        assert_eq!(eval("مطلق(-٥)."), Ok(Value::Integer(5)));
+        assert_eq!(eval("مطلق(-٢٫٥)."), Ok(Value::Number(2.5)));
+        assert_eq!(eval("تقريب(٢٫٥)."), Ok(Value::Integer(3)));
+        assert_eq!(eval("تقريب(٢٫٤)."), Ok(Value::Integer(2)));
+    }
+
+    #[test]
+    fn square_root_rejects_negative_numbers() {
+        assert_eq!(
+            eval("جذر(-٤)."),
+            Err("Cannot take the square root of a negative number at line 1, col 1".to_string())
+        );
+    }
+
+    #[test]
+    fn length_of_strings_and_arrays() {
+        assert_eq!(eval("طول(\"مرحبا\")."), Ok(Value::Integer(5)));
+        assert_eq!(eval("طول([١، ٢])."), Ok(Value::Integer(2)));
+        assert_eq!(eval("طول(\"\")."), Ok(Value::Integer(0)));
+    }
+
+    #[test]
+    fn length_rejects_scalars() {
+        assert_eq!(
+            eval("طول(٥)."),
+            Err(
+                "Function طول expects a string, array or map, got Integer(5) at line 1, col 1"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            eval("طول(نعم)."),
+            Err(
+                "Function طول expects a string, array or map, got Boolean(true) at line 1, col 1"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn raised_error_stops_the_program() {
+        let guard = "لو س < ٠ ف خطأ(\"سالب\"). تم\n\"بعد\".";
+        assert_eq!(
+            eval(&format!("متغير س = -١.\n{}", guard)),
+            Err("سالب".to_string())
+        );
+        assert_eq!(
+            eval(&format!("متغير س = ١.\n{}", guard)),
+            Ok(Value::String("بعد".to_string()))
+        );
+    }
+
+    #[test]
+    fn floor_and_ceil() {
+        assert_eq!(eval("أرضية(٣٫٧)."), Ok(Value::Integer(3)));
+        assert_eq!(eval("سقف(٣٫٢)."), Ok(Value::Integer(4)));
+        assert_eq!(eval("أرضية(-٣٫٢)."), Ok(Value::Integer(-4)));
+        assert!(eval("سقف(\"٣\").").is_err());
+    }
+
+    #[test]
+    fn conversions() {
+        assert!(matches!(eval("صحيح(٣٫٩)."), Ok(Value::Integer(3))));
+        assert!(matches!(eval("صحيح(-٣٫٩)."), Ok(Value::Integer(-3))));
+        assert!(matches!(eval("عشري(٢)."), Ok(Value::Number(n)) if n == 2.0));
+        assert_eq!(eval("نص(٤٢)."), Ok(Value::String("42".to_string())));
+        assert_eq!(eval("نص(نعم)."), Ok(Value::String("نعم".to_string())));
+        assert!(matches!(eval("عدد(\" ٤٢ \")."), Ok(Value::Integer(42))));
+        assert!(matches!(eval("عدد(\"-١٫٥\")."), Ok(Value::Number(n)) if n == -1.5));
+    }
+
+    #[test]
+    fn float_to_integer_rejects_values_out_of_range() {
+        assert_eq!(
+            eval("صحيح((٠٫٠ - ١) ^ ٠٫٥)."),
+            Err("Function صحيح cannot convert NaN to an integer at line 1, col 1".to_string())
+        );
+        assert_eq!(
+            eval("صحيح(١٠٫٠ ^ ٣٠)."),
+            Err(
+                "Function صحيح cannot convert 1000000000000000000000000000000 to an integer at line 1, col 1"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            eval("تقريب(١٠٫٠ ^ ٣٠)."),
+            Err(
+                "Function تقريب cannot convert 1000000000000000000000000000000 to an integer at line 1, col 1"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            eval("سقف(-(١٠٫٠ ^ ٣٠))."),
+            Err(
+                "Function سقف cannot convert -1000000000000000000000000000000 to an integer at line 1, col 1"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            eval("أرضية(١٠٫٠ ^ ٤٠٠)."),
+            Err("Function أرضية cannot convert inf to an integer at line 1, col 1".to_string())
+        );
+        assert_eq!(eval("صحيح(-(٢٫٠ ^ ٦٣))."), Ok(Value::Integer(i64::MIN)));
+    }
+
+    #[test]
+    fn parsing_a_bad_number_fails() {
+        assert_eq!(
+            eval("عدد(\"١٢أ\")."),
+            Err("Cannot convert \"١٢أ\" to a number at line 1, col 1".to_string())
+        );
+        assert_eq!(
+            eval("عدد(٥)."),
+            Err("Function عدد expects a string, got Integer(5) at line 1, col 1".to_string())
+        );
+    }
+
+    #[test]
+    fn min_and_max() {
+        assert!(matches!(eval("الأقصى(٣، ٧)."), Ok(Value::Integer(7))));
+        assert!(matches!(eval("الأدنى(٣، ٧)."), Ok(Value::Integer(3))));
+        assert!(matches!(eval("الأقصى(٣، ٢٫٥)."), Ok(Value::Number(n)) if n == 3.0));
+    }
+
+    #[test]
+    fn max_checks_its_arity() {
+        assert_eq!(
+            eval("الأقصى(٣)."),
+            Err("Function الأقصى expects 2 arguments, got 1 at line 1, col 1".to_string())
+        );
+    }
+
+    #[test]
+    fn contains_searches_strings_and_arrays() {
+        assert_eq!(eval("يحتوي(\"مرحبا\"، \"حب\")."), Ok(Value::Boolean(true)));
+        assert_eq!(
+            eval("يحتوي(\"مرحبا\"، \"سلام\")."),
+            Ok(Value::Boolean(false))
+        );
+        assert_eq!(eval("يحتوي([١، ٢، ٣]، ٢)."), Ok(Value::Boolean(true)));
+        assert_eq!(eval("يحتوي([١، ٢، ٣]، \"٢\")."), Ok(Value::Boolean(false)));
+        assert_eq!(
+            eval("يحتوي(٥، ٥)."),
+            Err(
+                "Function يحتوي cannot search for Integer(5) in Integer(5) at line 1, col 1"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn division_and_integer_division() {
+        assert!(matches!(eval("٦ / ٤."), Ok(Value::Number(n)) if n == 1.5));
+        assert!(matches!(eval("قسمة_صحيحة(٦، ٤)."), Ok(Value::Integer(1))));
+        assert_eq!(
+            eval("قسمة_صحيحة(٦، ٠)."),
+            Err("Division by zero at line 1, col 1".to_string())
+        );
+    }
+
+    #[test]
+    fn substring_counts_characters() {
+        assert_eq!(
+            eval("قص(\"مرحبا بكم\"، ٠، ٥)."),
+            Ok(Value::String("مرحبا".to_string()))
+        );
+        assert_eq!(
+            eval("قص(\"مرحبا\"، ٢، ٢)."),
+            Ok(Value::String(String::new()))
+        );
+        assert_eq!(
+            eval("قص(\"مرحبا\"، ٣، ٦)."),
+            Err("Invalid range 3..6 for string of length 5 at line 1, col 1".to_string())
+        );
+        assert_eq!(
+            eval("قص(\"مرحبا\"، ٣، ١)."),
+            Err("Invalid range 3..1 for string of length 5 at line 1, col 1".to_string())
+        );
+    }
+
+    #[test]
+    fn all_and_any() {
+        assert_eq!(eval("كله([نعم، نعم])."), Ok(Value::Boolean(true)));
+        assert_eq!(eval("كله([نعم، لا])."), Ok(Value::Boolean(false)));
+        assert_eq!(eval("كله([])."), Ok(Value::Boolean(true)));
+        assert_eq!(eval("أي([لا، نعم])."), Ok(Value::Boolean(true)));
+        assert_eq!(eval("أي([لا، لا])."), Ok(Value::Boolean(false)));
+        assert_eq!(eval("أي([])."), Ok(Value::Boolean(false)));
+        assert_eq!(
+            eval("أي([لا، ١])."),
+            Err(
+                "Function أي expects boolean elements, got Integer(1) at line 1, col 1".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn type_names() {
+        let cases = [
+            ("٥", "عدد"),
+            ("٢٫٥", "عدد"),
+            ("\"أ\"", "نص"),
+            ("لا", "منطقي"),
+            ("[١]", "مصفوفة"),
+            ("صف(س = ١)", "صف"),
+            ("خريطة(\"أ\": ١)", "خريطة"),
+            ("د", "دالة"),
+            ("فارغ", "فارغ"),
+        ];
+        for (value, name) in cases {
+            let source = format!("دالة د() ف تم\nنوع({}).", value);
+            assert_eq!(
+                eval(&source),
+                Ok(Value::String(name.to_string())),
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn reverse_and_sort() {
+        let numbers = |values: &[i64]| {
+            Ok(Value::Array(
+                values.iter().copied().map(Value::Integer).collect(),
+            ))
+        };
+        assert_eq!(eval("عكس([١، ٢، ٣])."), numbers(&[3, 2, 1]));
+        assert_eq!(eval("رتب([٣، ١، ٢])."), numbers(&[1, 2, 3]));
+        assert_eq!(
+            eval("رتب([\"ب\"، \"أ\"])."),
+            Ok(Value::Array(vec![
+                Value::String("أ".to_string()),
+                Value::String("ب".to_string())
+            ]))
+        );
+    }
+
+    #[test]
+    fn sort_rejects_mixed_types() {
+        assert_eq!(
+            eval("رتب([١، \"أ\"])."),
+            Err(
+                "Function رتب expects an array of only numbers or only strings at line 1, col 1"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn trim_and_normalize() {
+        assert_eq!(
+            eval("احذف_مسافات(\"  مرحبا \\t\")."),
+            Ok(Value::String("مرحبا".to_string()))
+        );
+        assert_eq!(eval("\"أحمد\" == \"احمد\"."), Ok(Value::Boolean(false)));
+        assert_eq!(
+            eval("طبع_موحد(\"أحمد\") == طبع_موحد(\"احمد\")."),
+            Ok(Value::Boolean(true))
+        );
+        assert_eq!(
+            eval("طبع_موحد(\"إسلام آمن\")."),
+            Ok(Value::String("اسلام امن".to_string()))
+        );
+    }
+}
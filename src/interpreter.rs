@@ -1,53 +1,374 @@
-use std::collections::HashMap;
+use indexmap::IndexMap;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::mem;
+use std::rc::Rc;
 
+use crate::builtins;
+use crate::lexer::Span;
 use crate::parser::{ASTNode, Operator};
 
+pub(crate) type Builtin = fn(&mut Interpreter, Vec<Value>) -> Result<Value, String>;
+
 #[derive(Debug, Clone)]
-enum Value {
+pub enum Value {
+    Integer(i64),
     Number(f64),
     String(String),
     Boolean(bool),
+    Array(Vec<Value>),
+    Record(IndexMap<String, Value>),
+    Map(IndexMap<MapKey, Value>),
+    Function(Rc<Function>),
+    Null,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MapKey {
+    Integer(i64),
+    String(String),
+}
+
+pub struct Function {
+    pub name: String,
+    pub(crate) parameters: Vec<String>,
+    pub(crate) body: Vec<ASTNode>,
+    // The local scopes around the declaration, shared so the body sees their later changes
+    closure: Vec<Rc<RefCell<Scope>>>,
+}
+
+// The closure is left out: it holds every variable around the declaration
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Function")
+            .field("name", &self.name)
+            .field("parameters", &self.parameters)
+            .finish_non_exhaustive()
+    }
+}
+
+// A precision such as `{:.2}` fixes the decimals of every float, including nested ones
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Integer(n) => write!(f, "{}", n),
+            Value::Number(n) => match f.precision() {
+                Some(precision) => write!(f, "{:.*}", precision, n),
+                None => write!(f, "{}", n),
+            },
+            Value::String(s) => f.write_str(s),
+            Value::Boolean(true) => write!(f, "نعم"),
+            Value::Boolean(false) => write!(f, "لا"),
+            Value::Array(elements) => {
+                write!(f, "[")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "، ")?;
+                    }
+                    write_nested(f, element)?;
+                }
+                write!(f, "]")
+            }
+            Value::Record(fields) => {
+                write!(f, "صف(")?;
+                for (i, (name, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "، ")?;
+                    }
+                    write!(f, "{} = ", name)?;
+                    write_nested(f, value)?;
+                }
+                write!(f, ")")
+            }
+            Value::Map(entries) => {
+                write!(f, "خريطة(")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "، ")?;
+                    }
+                    write!(f, "{}: ", key)?;
+                    write_nested(f, value)?;
+                }
+                write!(f, ")")
+            }
+            Value::Function(function) => write!(f, "دالة {}", function.name),
+            Value::Null => write!(f, "فارغ"),
+        }
+    }
+}
+
+fn write_nested(f: &mut fmt::Formatter, value: &Value) -> fmt::Result {
+    match f.precision() {
+        Some(precision) => write!(f, "{:.*}", precision, value),
+        None => write!(f, "{}", value),
+    }
 }
 
-struct Interpreter {
-    variables: HashMap<String, Value>,
+impl fmt::Display for MapKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MapKey::Integer(n) => write!(f, "{}", n),
+            MapKey::String(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+impl TryFrom<Value> for MapKey {
+    type Error = String;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Integer(n) => Ok(MapKey::Integer(n)),
+            Value::String(s) => Ok(MapKey::String(s)),
+            other => Err(format!(
+                "Map keys must be strings or integers, got {:?}",
+                other
+            )),
+        }
+    }
+}
+
+impl Value {
+    // Under the truthy policy only ٠, "", فارغ and لا are false; everything else is true
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Integer(n) => *n != 0,
+            Value::Number(n) => *n != 0.0,
+            Value::String(s) => !s.is_empty(),
+            Value::Boolean(b) => *b,
+            Value::Null => false,
+            Value::Array(_) | Value::Record(_) | Value::Map(_) | Value::Function(_) => true,
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Integer(_) => "Integer",
+            Value::Number(_) => "Number",
+            Value::String(_) => "String",
+            Value::Boolean(_) => "Boolean",
+            Value::Array(_) => "Array",
+            Value::Record(_) => "Record",
+            Value::Map(_) => "Map",
+            Value::Function(_) => "Function",
+            Value::Null => "Null",
+        }
+    }
+}
+
+// Integers and floats compare by numeric value, the same way `==` does, so collections
+// holding them agree with it too; functions only equal themselves
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Integer(l), Value::Integer(r)) => l == r,
+            (Value::Number(l), Value::Number(r)) => numbers_equal(*l, *r),
+            (Value::Integer(l), Value::Number(r)) | (Value::Number(r), Value::Integer(l)) => {
+                numbers_equal(*l as f64, *r)
+            }
+            (Value::String(l), Value::String(r)) => l == r,
+            (Value::Boolean(l), Value::Boolean(r)) => l == r,
+            (Value::Array(l), Value::Array(r)) => l == r,
+            (Value::Record(l), Value::Record(r)) => l == r,
+            (Value::Map(l), Value::Map(r)) => l == r,
+            (Value::Function(l), Value::Function(r)) => Rc::ptr_eq(l, r),
+            (Value::Null, Value::Null) => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Scope {
+    variables: IndexMap<String, Value>,
+    constants: HashSet<String>,
+}
+
+impl Scope {
+    fn define(&mut self, name: &str, value: Value, constant: bool) -> Result<(), String> {
+        if self.constants.contains(name) {
+            return Err(format!("Cannot reassign constant: {}", name));
+        }
+        if constant {
+            self.constants.insert(name.to_string());
+        }
+        self.variables.insert(name.to_string(), value);
+        Ok(())
+    }
+}
+
+pub struct Interpreter {
+    globals: Scope,
+    // Block and function scopes, innermost last; empty at the top level
+    scopes: Vec<Rc<RefCell<Scope>>>,
+    builtins: HashMap<&'static str, Builtin>,
+    input: Option<Box<dyn BufRead>>,
+    output: Box<dyn Write>,
+    max_iterations: Option<usize>,
+    truthy_conditions: bool,
+    float_precision: Option<usize>,
+    returning: Option<Value>,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Interpreter {
-    fn new() -> Self {
+    pub fn new() -> Self {
         Interpreter {
-            variables: HashMap::new(),
+            globals: Scope::default(),
+            scopes: Vec::new(),
+            builtins: builtins::all().into_iter().collect(),
+            input: None,
+            output: Box::new(io::stdout()),
+            max_iterations: None,
+            truthy_conditions: false,
+            float_precision: None,
+            returning: None,
         }
     }
 
-    fn interpret(&mut self, ast: &[ASTNode]) -> Result<(), String> {
+    // Where اقرأ reads lines from instead of stdin
+    pub fn with_input(mut self, input: impl BufRead + 'static) -> Self {
+        self.input = Some(Box::new(input));
+        self
+    }
+
+    pub fn with_output(mut self, output: impl Write + 'static) -> Self {
+        self.output = Box::new(output);
+        self
+    }
+
+    // Lets conditions accept any value using `Value::is_truthy`; by default they must be booleans
+    pub fn set_truthy_conditions(&mut self, enabled: bool) {
+        self.truthy_conditions = enabled;
+    }
+
+    fn evaluate_condition(&mut self, condition: &ASTNode) -> Result<bool, String> {
+        match self.execute(condition)? {
+            Value::Boolean(b) => Ok(b),
+            value if self.truthy_conditions => Ok(value.is_truthy()),
+            _ => Err("Condition must evaluate to a boolean".to_string()),
+        }
+    }
+
+    // Fixes how many decimals printed floats show; by default they use the shortest exact form
+    pub fn set_float_precision(&mut self, precision: Option<usize>) {
+        self.float_precision = precision;
+    }
+
+    pub fn format_value(&self, value: &Value) -> String {
+        match self.float_precision {
+            Some(precision) => format!("{:.*}", precision, value),
+            None => value.to_string(),
+        }
+    }
+
+    // Caps how many times a single بينما or كرر loop may run; unlimited by default
+    pub fn set_max_iterations(&mut self, limit: Option<usize>) {
+        self.max_iterations = limit;
+    }
+
+    fn check_iterations(&self, iterations: usize) -> Result<(), String> {
+        match self.max_iterations {
+            Some(limit) if iterations > limit => {
+                Err(format!("Loop exceeded the maximum of {} iterations", limit))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Where اطبع writes; stdout unless the interpreter was built with `with_output`
+    pub fn output(&mut self) -> &mut dyn Write {
+        &mut self.output
+    }
+
+    fn write(&mut self, text: &str) -> Result<(), String> {
+        self.output
+            .write_all(text.as_bytes())
+            .and_then(|_| self.output.flush())
+            .map_err(|e| format!("Could not write output: {}", e))
+    }
+
+    pub(crate) fn read_line(&mut self, prompt: Option<&str>) -> Result<String, String> {
+        if let Some(prompt) = prompt {
+            self.write(prompt)?;
+        }
+        let mut line = String::new();
+        let result = match &mut self.input {
+            Some(input) => input.read_line(&mut line),
+            None => io::stdin().read_line(&mut line),
+        };
+        result.map_err(|e| format!("Could not read input: {}", e))?;
+        Ok(line.trim_end_matches(['\n', '\r']).to_string())
+    }
+
+    pub fn eval(&mut self, input: &str) -> Result<Value, String> {
+        let ast = crate::parse_source(input).map_err(|errors| errors.join("\n"))?;
+        self.interpret(&ast)
+    }
+
+    pub fn interpret(&mut self, ast: &[ASTNode]) -> Result<Value, String> {
+        let mut last = Value::Null;
         for node in ast {
-            self.execute(node)?;
+            last = self.execute(node)?;
+            // A top-level return ends the program with its value
+            if let Some(value) = self.returning.take() {
+                return Ok(value);
+            }
         }
-        Ok(())
+        Ok(last)
     }
 
     fn execute(&mut self, node: &ASTNode) -> Result<Value, String> {
         match node {
+            ASTNode::Integer(n) => Ok(Value::Integer(*n)),
             ASTNode::Number(n) => Ok(Value::Number(*n)),
             ASTNode::StringLiteral(s) => Ok(Value::String(s.to_string())),
-            ASTNode::Variable(name) => self
-                .variables
-                .get(name)
-                .cloned()
-                .ok_or_else(|| format!("Undefined variable: {}", name)),
+            ASTNode::Variable { name, span } => self
+                .lookup(name)
+                .ok_or_else(|| format!("Undefined variable: {} at {}", name, span)),
             ASTNode::BinaryOp {
                 left,
                 operator,
                 right,
+                span,
             } => {
                 let left_val = self.execute(left)?;
+                // Short-circuit: skip the right operand when the left one decides the result
+                if let (Operator::And, Value::Boolean(false))
+                | (Operator::Or, Value::Boolean(true)) = (operator, &left_val)
+                {
+                    return Ok(left_val);
+                }
                 let right_val = self.execute(right)?;
                 self.evaluate_binary_op(operator, left_val, right_val)
+                    .map_err(|e| format!("{} at {}", e, span))
+            }
+            ASTNode::UnaryOp { operator, operand } => {
+                let val = self.execute(operand)?;
+                self.evaluate_unary_op(operator, val)
             }
             ASTNode::VariableDeclaration { variable, value } => {
                 let val = self.execute(value)?;
-                self.variables.insert(variable.clone(), val.clone());
+                self.declare(variable, val.clone(), false)?;
+                Ok(val)
+            }
+            ASTNode::ConstantDeclaration { variable, value } => {
+                let val = self.execute(value)?;
+                self.declare(variable, val.clone(), true)?;
+                Ok(val)
+            }
+            ASTNode::Assignment { variable, value } => {
+                // Check the target first, so in `أ = ب = ٥.` an undeclared أ leaves ب untouched
+                self.check_assignable(variable)?;
+                let val = self.execute(value)?;
+                self.assign(variable, val.clone())?;
                 Ok(val)
             }
             ASTNode::IfStatement {
@@ -55,24 +376,343 @@ impl Interpreter {
                 then_branch,
                 else_branch,
             } => {
-                let condition_value = self.execute(condition)?;
-                match condition_value {
-                    Value::Boolean(true) => {
-                        for stmt in then_branch {
-                            self.execute(stmt)?;
-                        }
+                let branch = if self.evaluate_condition(condition)? {
+                    Some(then_branch)
+                } else {
+                    else_branch.as_ref()
+                };
+                // The if evaluates to the last value of the taken branch, null if none ran
+                match branch {
+                    Some(statements) => self.execute_block(statements),
+                    None => Ok(Value::Null),
+                }
+            }
+            ASTNode::ConditionalExpr {
+                condition,
+                then_value,
+                else_value,
+            } => {
+                if self.evaluate_condition(condition)? {
+                    self.execute(then_value)
+                } else {
+                    self.execute(else_value)
+                }
+            }
+            ASTNode::WhileLoop { condition, body } => {
+                let mut iterations = 0;
+                while self.evaluate_condition(condition)? {
+                    iterations += 1;
+                    self.check_iterations(iterations)?;
+                    self.execute_block(body)?;
+                    if let Some(value) = &self.returning {
+                        return Ok(value.clone());
                     }
-                    Value::Boolean(false) if else_branch.is_some() => {
-                        for stmt in else_branch.as_ref().unwrap() {
-                            self.execute(stmt)?;
-                        }
+                }
+                Ok(Value::Null)
+            }
+            ASTNode::ForEach {
+                variable,
+                iterable,
+                body,
+            } => {
+                let elements = match self.execute(iterable)? {
+                    Value::Array(elements) => elements,
+                    other => return Err(format!("Cannot iterate over {:?}", other)),
+                };
+                for element in elements {
+                    self.scopes.push(Rc::default());
+                    self.declare(variable, element, false)?;
+                    let result = self.execute_block(body);
+                    self.pop_scope();
+                    result?;
+                    if let Some(value) = &self.returning {
+                        return Ok(value.clone());
                     }
-                    Value::Boolean(false) => {}
-                    _ => return Err("Condition must evaluate to a boolean".to_string()),
                 }
-                Ok(Value::Boolean(true)) // If statements always evaluate to true in this implementation
+                Ok(Value::Null)
+            }
+            ASTNode::Repeat { count, body } => {
+                let count = match self.execute(count)? {
+                    Value::Integer(n) if n >= 0 => n,
+                    other => {
+                        return Err(format!(
+                            "Repeat count must be a non-negative integer, got {:?}",
+                            other
+                        ))
+                    }
+                };
+                for iteration in 1..=count as usize {
+                    self.check_iterations(iteration)?;
+                    self.execute_block(body)?;
+                    if let Some(value) = &self.returning {
+                        return Ok(value.clone());
+                    }
+                }
+                Ok(Value::Null)
             }
             ASTNode::BooleanLiteral(b) => Ok(Value::Boolean(*b)),
+            ASTNode::NullLiteral => Ok(Value::Null),
+            ASTNode::ArrayLiteral(elements) => {
+                let mut values = Vec::new();
+                for element in elements {
+                    values.push(self.execute(element)?);
+                }
+                Ok(Value::Array(values))
+            }
+            ASTNode::RecordLiteral(fields) => {
+                let mut values = IndexMap::new();
+                for (name, value) in fields {
+                    values.insert(name.clone(), self.execute(value)?);
+                }
+                Ok(Value::Record(values))
+            }
+            ASTNode::MapLiteral(entries) => {
+                let mut values = IndexMap::new();
+                for (key, value) in entries {
+                    let key = MapKey::try_from(self.execute(key)?)?;
+                    values.insert(key, self.execute(value)?);
+                }
+                Ok(Value::Map(values))
+            }
+            ASTNode::Index { target, index } => {
+                let target = self.execute(target)?;
+                let index = self.execute(index)?;
+                self.evaluate_index(target, index)
+            }
+            ASTNode::FieldAccess { target, field } => match self.execute(target)? {
+                Value::Record(fields) => fields
+                    .get(field)
+                    .cloned()
+                    .ok_or_else(|| format!("Record has no field named {}", field)),
+                other => Err(format!("Cannot read field {} of {:?}", field, other)),
+            },
+            ASTNode::IndexAssignment {
+                variable,
+                index,
+                value,
+            } => {
+                self.check_assignable(variable)?;
+                let index = self.execute(index)?;
+                let val = self.execute(value)?;
+                self.assign_index(variable, index, val.clone())?;
+                Ok(val)
+            }
+            ASTNode::Print { value, newline } => {
+                let value = self.execute(value)?;
+                let mut text = self.format_value(&value);
+                if *newline {
+                    text.push('\n');
+                }
+                self.write(&text)?;
+                Ok(Value::Null)
+            }
+            ASTNode::FunctionDeclaration {
+                name,
+                parameters,
+                body,
+            } => {
+                let function = Value::Function(Rc::new(Function {
+                    name: name.clone(),
+                    parameters: parameters.clone(),
+                    body: body.clone(),
+                    closure: self.scopes.clone(),
+                }));
+                self.declare(name, function.clone(), false)?;
+                Ok(function)
+            }
+            ASTNode::Call {
+                callee,
+                arguments,
+                span,
+            } => {
+                let mut args = Vec::new();
+                for argument in arguments {
+                    args.push(self.execute(argument)?);
+                }
+                self.call_function(callee, args, *span)
+            }
+            ASTNode::Return { value, .. } => {
+                let val = self.execute(value)?;
+                self.returning = Some(val.clone());
+                Ok(val)
+            }
+        }
+    }
+
+    fn declare(&mut self, name: &str, value: Value, constant: bool) -> Result<(), String> {
+        match self.scopes.last() {
+            Some(scope) => scope.borrow_mut().define(name, value, constant),
+            None => self.globals.define(name, value, constant),
+        }
+    }
+
+    // Looks a name up from the innermost scope out to the globals
+    fn lookup(&self, name: &str) -> Option<Value> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.borrow().variables.get(name).cloned())
+            .or_else(|| self.globals.variables.get(name).cloned())
+    }
+
+    fn check_assignable(&self, name: &str) -> Result<(), String> {
+        let is_constant = |scope: &Scope| {
+            scope
+                .variables
+                .contains_key(name)
+                .then(|| scope.constants.contains(name))
+        };
+        let constant = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| is_constant(&scope.borrow()))
+            .or_else(|| is_constant(&self.globals))
+            .ok_or_else(|| format!("Assignment to undeclared variable: {}", name))?;
+        if constant {
+            return Err(format!("Cannot reassign constant: {}", name));
+        }
+        Ok(())
+    }
+
+    fn assign(&mut self, name: &str, value: Value) -> Result<(), String> {
+        self.check_assignable(name)?;
+        match self
+            .scopes
+            .iter()
+            .rev()
+            .find(|scope| scope.borrow().variables.contains_key(name))
+        {
+            Some(scope) => scope.borrow_mut().variables.insert(name.to_string(), value),
+            None => self.globals.variables.insert(name.to_string(), value),
+        };
+        Ok(())
+    }
+
+    fn execute_block(&mut self, statements: &[ASTNode]) -> Result<Value, String> {
+        self.scopes.push(Rc::default());
+        let result = self.execute_statements(statements);
+        self.pop_scope();
+        result
+    }
+
+    // A function declared in the ending scope captures that scope, so the scope's binding to
+    // it is dropped to break the cycle; a function that outlives it binds its own name on call
+    fn pop_scope(&mut self) {
+        let scope = self.scopes.pop().expect("pop_scope follows a push");
+        scope.borrow_mut().variables.retain(|_, value| match value {
+            Value::Function(function) => !function
+                .closure
+                .last()
+                .is_some_and(|captured| Rc::ptr_eq(captured, &scope)),
+            _ => true,
+        });
+    }
+
+    fn execute_statements(&mut self, statements: &[ASTNode]) -> Result<Value, String> {
+        let mut result = Value::Null;
+        for stmt in statements {
+            result = self.execute(stmt)?;
+            if self.returning.is_some() {
+                break;
+            }
+        }
+        Ok(result)
+    }
+
+    // The body sees the scopes around its declaration and its own frame, never the caller's
+    // locals: it can read and reassign those outer variables, while its parameters shadow them
+    fn call_function(&mut self, name: &str, args: Vec<Value>, span: Span) -> Result<Value, String> {
+        let function = match self.lookup(name) {
+            Some(Value::Function(function)) => function,
+            Some(_) => return Err(format!("{} is not a function at {}", name, span)),
+            None => match self.builtins.get(name) {
+                // خطأ raises the program's own message, which is reported as written
+                Some(builtin) if name == "خطأ" => return builtin(self, args),
+                Some(builtin) => {
+                    return builtin(self, args).map_err(|e| format!("{} at {}", e, span))
+                }
+                None => return Err(format!("Undefined function: {} at {}", name, span)),
+            },
+        };
+        if args.len() != function.parameters.len() {
+            return Err(format!(
+                "Function {} expects {} arguments, got {} at {}",
+                name,
+                function.parameters.len(),
+                args.len(),
+                span
+            ));
+        }
+
+        let caller_scopes = mem::replace(&mut self.scopes, function.closure.clone());
+        let mut variables = IndexMap::new();
+        variables.insert(function.name.clone(), Value::Function(Rc::clone(&function)));
+        variables.extend(function.parameters.iter().cloned().zip(args));
+        self.scopes.push(Rc::new(RefCell::new(Scope {
+            variables,
+            constants: HashSet::new(),
+        })));
+        let result = self.execute_block(&function.body);
+        self.scopes = caller_scopes;
+
+        let value = result?;
+        Ok(self.returning.take().unwrap_or(value))
+    }
+
+    fn evaluate_index(&self, target: Value, index: Value) -> Result<Value, String> {
+        match target {
+            Value::Array(mut elements) => {
+                let position = position(index, elements.len())?;
+                Ok(elements.swap_remove(position))
+            }
+            Value::String(s) => {
+                let position = position(index, s.chars().count())?;
+                Ok(Value::String(s.chars().nth(position).unwrap().to_string()))
+            }
+            Value::Map(entries) => {
+                let key = MapKey::try_from(index)?;
+                entries
+                    .get(&key)
+                    .cloned()
+                    .ok_or_else(|| format!("Map has no key {}", key))
+            }
+            other => Err(format!("Cannot index into {:?}", other)),
+        }
+    }
+
+    fn assign_index(&mut self, name: &str, index: Value, value: Value) -> Result<(), String> {
+        let mut container = self
+            .lookup(name)
+            .ok_or_else(|| format!("Assignment to undeclared variable: {}", name))?;
+        match &mut container {
+            Value::Array(elements) => {
+                let position = position(index, elements.len())?;
+                elements[position] = value;
+            }
+            Value::Map(entries) => {
+                entries.insert(MapKey::try_from(index)?, value);
+            }
+            other => return Err(format!("Cannot assign to an index of {:?}", other)),
+        }
+        self.assign(name, container)
+    }
+
+    fn evaluate_unary_op(&self, operator: &Operator, operand: Value) -> Result<Value, String> {
+        match (operator, operand) {
+            (Operator::Minus, Value::Integer(n)) => n
+                .checked_neg()
+                .map(Value::Integer)
+                .ok_or_else(|| "Integer overflow".to_string()),
+            (Operator::Minus, Value::Number(n)) => Ok(Value::Number(-n)),
+            (Operator::Not, Value::Boolean(b)) => Ok(Value::Boolean(!b)),
+            (Operator::Not, operand) => {
+                Err(format!("Logical not requires a boolean, got {:?}", operand))
+            }
+            (operator, operand) => Err(format!(
+                "Unknown unary operator {:?} for {:?}",
+                operator, operand
+            )),
         }
     }
 
@@ -83,51 +723,1153 @@ impl Interpreter {
         right: Value,
     ) -> Result<Value, String> {
         match (left, right) {
-            (Value::Number(l), Value::Number(r)) => {
-                return match operator {
-                    Operator::Plus => Ok(Value::Number(l + r)),
-                    Operator::Minus => Ok(Value::Number(l - r)),
-                    Operator::Multiply => Ok(Value::Number(l * r)),
-                    Operator::Divide => {
-                        if r == 0.0 {
-                            return Err("Division by zero".to_string());
-                        }
-                        Ok(Value::Number(l / r))
+            (Value::Integer(l), Value::Integer(r)) => match operator {
+                Operator::Plus => checked_integer(l.checked_add(r)),
+                Operator::Minus => checked_integer(l.checked_sub(r)),
+                Operator::Multiply => checked_integer(l.checked_mul(r)),
+                Operator::Divide => {
+                    if r == 0 {
+                        return Err("Division by zero".to_string());
                     }
-                    Operator::LT => Ok(Value::Boolean(l < r)),
-                    Operator::GT => Ok(Value::Boolean(l > r)),
-                    Operator::LTE => Ok(Value::Boolean(l <= r)),
-                    Operator::GTE => Ok(Value::Boolean(l >= r)),
-                    Operator::EQ => Ok(Value::Boolean((l - r).abs() < f64::EPSILON)),
-                    Operator::NEQ => Ok(Value::Boolean((l - r).abs() >= f64::EPSILON)),
-                    _ => return Err(format!("Unknown operator for numbers: {:?}", operator)),
-                };
+                    // Division always yields a float; قسمة_صحيحة divides integers
+                    Ok(Value::Number(l as f64 / r as f64))
+                }
+                Operator::Modulo => {
+                    if r == 0 {
+                        return Err("Modulo by zero".to_string());
+                    }
+                    checked_integer(l.checked_rem(r))
+                }
+                Operator::Power => match u32::try_from(r) {
+                    Ok(exponent) => checked_integer(l.checked_pow(exponent)),
+                    Err(_) => Ok(Value::Number((l as f64).powf(r as f64))),
+                },
+                Operator::LT => Ok(Value::Boolean(l < r)),
+                Operator::GT => Ok(Value::Boolean(l > r)),
+                Operator::LTE => Ok(Value::Boolean(l <= r)),
+                Operator::GTE => Ok(Value::Boolean(l >= r)),
+                Operator::EQ => Ok(Value::Boolean(l == r)),
+                Operator::NEQ => Ok(Value::Boolean(l != r)),
+                _ => Err(format!("Unknown operator for numbers: {:?}", operator)),
+            },
+            (Value::Integer(l), Value::Number(r)) => {
+                self.evaluate_binary_op(operator, Value::Number(l as f64), Value::Number(r))
+            }
+            (Value::Number(l), Value::Integer(r)) => {
+                self.evaluate_binary_op(operator, Value::Number(l), Value::Number(r as f64))
             }
+            (Value::Number(l), Value::Number(r)) => match operator {
+                Operator::Plus => Ok(Value::Number(l + r)),
+                Operator::Minus => Ok(Value::Number(l - r)),
+                Operator::Multiply => Ok(Value::Number(l * r)),
+                Operator::Divide => {
+                    if r == 0.0 {
+                        return Err("Division by zero".to_string());
+                    }
+                    Ok(Value::Number(l / r))
+                }
+                Operator::Modulo => {
+                    if r == 0.0 {
+                        return Err("Modulo by zero".to_string());
+                    }
+                    Ok(Value::Number(l % r))
+                }
+                Operator::Power => Ok(Value::Number(l.powf(r))),
+                Operator::LT => Ok(Value::Boolean(l < r)),
+                Operator::GT => Ok(Value::Boolean(l > r)),
+                Operator::LTE => Ok(Value::Boolean(l <= r)),
+                Operator::GTE => Ok(Value::Boolean(l >= r)),
+                Operator::EQ => Ok(Value::Boolean(numbers_equal(l, r))),
+                Operator::NEQ => Ok(Value::Boolean(!numbers_equal(l, r))),
+                _ => Err(format!("Unknown operator for numbers: {:?}", operator)),
+            },
             (Value::Boolean(l), Value::Boolean(r)) => {
                 let result = match operator {
                     Operator::And => l && r,
                     Operator::Or => l || r,
+                    Operator::EQ => l == r,
+                    Operator::NEQ => l != r,
+                    Operator::Modulo => {
+                        return Err(
+                            "Modulo (٪) is only defined for numbers, not booleans".to_string()
+                        )
+                    }
                     _ => return Err(format!("Unknown operator for booleans: {:?}", operator)),
                 };
                 Ok(Value::Boolean(result))
             }
-            (Value::String(_), Value::String(_)) => todo!(),
-            _ => Err("Type mismatch in binary operation".to_string()),
+            (Value::String(l), Value::String(r)) => match operator {
+                Operator::Plus => Ok(Value::String(l + &r)),
+                Operator::EQ => Ok(Value::Boolean(l == r)),
+                Operator::NEQ => Ok(Value::Boolean(l != r)),
+                Operator::LT => Ok(Value::Boolean(l < r)),
+                Operator::GT => Ok(Value::Boolean(l > r)),
+                Operator::LTE => Ok(Value::Boolean(l <= r)),
+                Operator::GTE => Ok(Value::Boolean(l >= r)),
+                Operator::Modulo => {
+                    Err("Modulo (٪) is only defined for numbers, not strings".to_string())
+                }
+                _ => Err(format!("Unknown operator for strings: {:?}", operator)),
+            },
+            (Value::String(s), count @ (Value::Integer(_) | Value::Number(_)))
+            | (count @ (Value::Integer(_) | Value::Number(_)), Value::String(s))
+                if *operator == Operator::Multiply =>
+            {
+                match count {
+                    Value::Integer(n) if n >= 0 => repeat(&s, n as usize),
+                    count => Err(format!(
+                        "A string can only be repeated a non-negative integer number of times, got {:?}",
+                        count
+                    )),
+                }
+            }
+            (Value::String(l), r @ (Value::Integer(_) | Value::Number(_)))
+                if *operator == Operator::Plus =>
+            {
+                Ok(Value::String(format!("{}{}", l, r)))
+            }
+            (l @ (Value::Integer(_) | Value::Number(_)), Value::String(r))
+                if *operator == Operator::Plus =>
+            {
+                Ok(Value::String(format!("{}{}", l, r)))
+            }
+            (Value::Array(mut l), Value::Array(r)) => match operator {
+                Operator::Plus => {
+                    l.extend(r);
+                    Ok(Value::Array(l))
+                }
+                Operator::EQ => Ok(Value::Boolean(l == r)),
+                Operator::NEQ => Ok(Value::Boolean(l != r)),
+                _ => Err(format!("Unknown operator for arrays: {:?}", operator)),
+            },
+            (Value::Record(l), Value::Record(r)) => match operator {
+                Operator::EQ => Ok(Value::Boolean(l == r)),
+                Operator::NEQ => Ok(Value::Boolean(l != r)),
+                _ => Err(format!("Unknown operator for records: {:?}", operator)),
+            },
+            (Value::Map(l), Value::Map(r)) => match operator {
+                Operator::EQ => Ok(Value::Boolean(l == r)),
+                Operator::NEQ => Ok(Value::Boolean(l != r)),
+                _ => Err(format!("Unknown operator for maps: {:?}", operator)),
+            },
+            (Value::Null, Value::Null) => match operator {
+                Operator::EQ => Ok(Value::Boolean(true)),
+                Operator::NEQ => Ok(Value::Boolean(false)),
+                _ => Err(format!("Unknown operator for null: {:?}", operator)),
+            },
+            // Values of different types are simply unequal, but they still can't be ordered
+            (l, r) if *operator == Operator::EQ => Ok(Value::Boolean(l == r)),
+            (l, r) if *operator == Operator::NEQ => Ok(Value::Boolean(l != r)),
+            (l, r) => Err(format!(
+                "Cannot apply {} between {} and {}",
+                operator,
+                l.type_name(),
+                r.type_name()
+            )),
         }
     }
 
-    fn get_variable(&self, name: &str) -> Option<&Value> {
-        self.variables.get(name)
+    // Reads a global; block and function locals are gone once a run ends
+    pub fn get_variable(&self, name: &str) -> Option<&Value> {
+        self.globals.variables.get(name)
+    }
+
+    pub fn variables(&self) -> &IndexMap<String, Value> {
+        &self.globals.variables
+    }
+
+    // One `اسم = قيمة` line per global variable, in declaration order
+    pub fn snapshot(&self) -> String {
+        self.variables()
+            .iter()
+            .map(|(name, value)| format!("{} = {}\n", name, self.format_value(value)))
+            .collect()
     }
 }
 
-pub fn run(ast: Vec<ASTNode>) {
-    let mut interpreter = Interpreter::new();
-    match interpreter.interpret(&ast) {
-        Ok(()) => {
-            println!("Interpretation successful.");
-            println!("Variables: {:#?}", interpreter.variables);
+// Indices are zero-based; negative indices count back from the end, so -1 is the last element
+fn position(index: Value, length: usize) -> Result<usize, String> {
+    let index = match index {
+        Value::Integer(i) => i,
+        other => return Err(format!("Index must be an integer, got {:?}", other)),
+    };
+    let position = if index < 0 {
+        length.checked_sub(index.unsigned_abs() as usize)
+    } else {
+        Some(index as usize).filter(|&i| i < length)
+    };
+    position.ok_or_else(|| format!("Index {} is out of range for length {}", index, length))
+}
+
+// Floats within rounding error of each other are equal, so `٠٫١ + ٠٫٢ == ٠٫٣`
+fn numbers_equal(l: f64, r: f64) -> bool {
+    (l - r).abs() < f64::EPSILON
+}
+
+// Like `str::repeat`, but too long a result is an error rather than a panic
+fn repeat(s: &str, count: usize) -> Result<Value, String> {
+    let too_long = || "Repeated string is too long".to_string();
+    let length = s.len().checked_mul(count).ok_or_else(too_long)?;
+    let mut result = String::new();
+    result.try_reserve_exact(length).map_err(|_| too_long())?;
+    for _ in 0..count {
+        result.push_str(s);
+    }
+    Ok(Value::String(result))
+}
+
+fn checked_integer(result: Option<i64>) -> Result<Value, String> {
+    result
+        .map(Value::Integer)
+        .ok_or_else(|| "Integer overflow".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::io::Cursor;
+
+    // A `Vec<u8>` sink that stays readable after a clone is handed to the interpreter
+    #[derive(Clone, Default)]
+    struct Sink(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Sink {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Sink {
+        fn contents(&self) -> String {
+            String::from_utf8(self.0.borrow().clone()).unwrap()
+        }
+    }
+
+    fn eval(source: &str) -> Result<Value, String> {
+        Interpreter::new().eval(source)
+    }
+
+    fn output(source: &str) -> String {
+        let sink = Sink::default();
+        Interpreter::new()
+            .with_output(sink.clone())
+            .eval(source)
+            .unwrap();
+        sink.contents()
+    }
+
+    #[test]
+    fn function_reads_a_global_constant() {
+        let source = "ثابت ط = ٣.\nدالة ضعف() ف أرجع ط * ٢. تم\nضعف().";
+        assert_eq!(eval(source), Ok(Value::Integer(6)));
+    }
+
+    #[test]
+    fn parameter_shadows_a_global_of_the_same_name() {
+        let mut interpreter = Interpreter::new();
+        let source = "متغير س = ١.\nدالة زد(س) ف س = س + ١٠. أرجع س. تم\nزد(٥).";
+        assert_eq!(interpreter.eval(source), Ok(Value::Integer(15)));
+        assert_eq!(interpreter.get_variable("س"), Some(&Value::Integer(1)));
+    }
+
+    #[test]
+    fn nested_function_sees_the_enclosing_locals() {
+        let source = "دالة خارج() ف متغير ع = ٥. دالة داخل() ف أرجع ع. تم أرجع داخل(). تم
+خارج().";
+        assert_eq!(eval(source), Ok(Value::Integer(5)));
+    }
+
+    #[test]
+    fn nested_function_can_call_itself() {
+        let source = "دالة خارج() ف
+دالة عد(ن) ف أرجع إذا ن == ٠ فإن ٠ وإلا ن + عد(ن - ١). تم
+أرجع عد(٣).
+تم
+خارج().";
+        assert_eq!(eval(source), Ok(Value::Integer(6)));
+    }
+
+    #[test]
+    fn returned_function_keeps_its_captured_variables() {
+        let source = "دالة عداد() ف
+متغير ع = ٠.
+دالة زد() ف ع = ع + ١. أرجع ع. تم
+أرجع زد.
+تم
+متغير ز = عداد().
+ز().
+ز().";
+        assert_eq!(eval(source), Ok(Value::Integer(2)));
+    }
+
+    #[test]
+    fn returned_function_does_not_keep_itself_alive() {
+        let mut interpreter = Interpreter::new();
+        let source = "دالة خارج() ف
+متغير ع = مدى(٠، ١٠٠٠).
+دالة داخل() ف أرجع ع. تم
+أرجع داخل.
+تم
+متغير د = خارج().";
+        interpreter.eval(source).unwrap();
+        let Some(Value::Function(function)) = interpreter.get_variable("د") else {
+            panic!("د should hold a function");
+        };
+        assert_eq!(Rc::strong_count(function), 1);
+        assert!(function
+            .closure
+            .iter()
+            .all(|scope| Rc::strong_count(scope) == 1));
+        assert_eq!(interpreter.eval("د()[-١]."), Ok(Value::Integer(999)));
+    }
+
+    #[test]
+    fn returned_function_can_still_call_itself() {
+        let source = "دالة خارج() ف
+دالة عد(ن) ف أرجع إذا ن == ٠ فإن ٠ وإلا ن + عد(ن - ١). تم
+أرجع عد.
+تم
+متغير ع = خارج().
+ع(٤).";
+        assert_eq!(eval(source), Ok(Value::Integer(10)));
+    }
+
+    #[test]
+    fn function_sees_its_declaration_scope_not_the_callers() {
+        let source = "متغير ع = \"عام\".
+دالة ق() ف أرجع ع. تم
+دالة ر() ف متغير ع = \"محلي لر\". أرجع ق(). تم
+ر().";
+        assert_eq!(eval(source), Ok(Value::String("عام".to_string())));
+
+        let source = "دالة غير() ف ص = ٢. تم
+دالة ر() ف متغير ص = ١. غير(). أرجع ص. تم
+ر().";
+        assert_eq!(
+            eval(source),
+            Err("Assignment to undeclared variable: ص".to_string())
+        );
+    }
+
+    #[test]
+    fn two_level_index() {
+        assert_eq!(
+            eval("متغير بيانات = [[١، ٢]، [٣، ٤]].\nبيانات[١][٠]."),
+            Ok(Value::Integer(3))
+        );
+        assert_eq!(
+            eval("[[١، ٢]][٠][٥]."),
+            Err("Index 5 is out of range for length 2".to_string())
+        );
+    }
+
+    #[test]
+    fn field_then_index_chain() {
+        let source = "متغير سجل = صف(قيمة = [١٠، ٢٠، ٣٠]، داخلي = صف(س = ٥)).";
+        assert_eq!(
+            eval(&format!("{}\nسجل.قيمة[٢].", source)),
+            Ok(Value::Integer(30))
+        );
+        assert_eq!(
+            eval(&format!("{}\nسجل.داخلي.س.", source)),
+            Ok(Value::Integer(5))
+        );
+        assert_eq!(
+            eval(&format!("{}\nسجل.غير[٠].", source)),
+            Err("Record has no field named غير".to_string())
+        );
+    }
+
+    #[test]
+    fn string_repetition() {
+        assert_eq!(eval("\"-\" * ٣."), Ok(Value::String("---".to_string())));
+        assert_eq!(eval("٢ * \"أب\"."), Ok(Value::String("أبأب".to_string())));
+        assert_eq!(eval("\"أب\" * ٠."), Ok(Value::String(String::new())));
+    }
+
+    #[test]
+    fn string_repetition_rejects_bad_counts() {
+        assert_eq!(
+            eval("\"-\" * -١."),
+            Err("A string can only be repeated a non-negative integer number of times, got Integer(-1) at line 1, col 5".to_string())
+        );
+        assert_eq!(
+            eval("\"-\" * ٣٫٠."),
+            Err("A string can only be repeated a non-negative integer number of times, got Number(3.0) at line 1, col 5".to_string())
+        );
+        assert_eq!(
+            eval("\"أب\" * 9223372036854775807."),
+            Err("Repeated string is too long at line 1, col 6".to_string())
+        );
+    }
+
+    #[test]
+    fn collections_compare_structurally() {
+        assert_eq!(eval("[١، ٢] == [١، ٢]."), Ok(Value::Boolean(true)));
+        assert_eq!(eval("[١، ٢] != [٢، ١]."), Ok(Value::Boolean(true)));
+        assert_eq!(eval("[[١]، [٢]] == [[١]، [٢]]."), Ok(Value::Boolean(true)));
+        assert_eq!(
+            eval("صف(س = ١، ص = [٢]) == صف(س = ١، ص = [٢])."),
+            Ok(Value::Boolean(true))
+        );
+    }
+
+    #[test]
+    fn collections_compare_numbers_like_scalars() {
+        assert_eq!(eval("٠٫١ + ٠٫٢ == ٠٫٣."), Ok(Value::Boolean(true)));
+        assert_eq!(eval("[٠٫١ + ٠٫٢] == [٠٫٣]."), Ok(Value::Boolean(true)));
+        assert_eq!(eval("[١] == [١٫٠]."), Ok(Value::Boolean(true)));
+        assert_eq!(
+            eval("خريطة(\"أ\": ٠٫١ + ٠٫٢) == خريطة(\"أ\": ٠٫٣)."),
+            Ok(Value::Boolean(true))
+        );
+    }
+
+    #[test]
+    fn nested_array_prints_recursively() {
+        assert_eq!(
+            eval("[[١، ٢]، [٣، [\"أ\"]]].").unwrap().to_string(),
+            "[[1، 2]، [3، [أ]]]"
+        );
+    }
+
+    #[test]
+    fn output_goes_to_the_injected_sink() {
+        assert_eq!(output("اطبع ١.\nاطبع_سطر \"أ\"."), "1أ\n");
+    }
+
+    #[test]
+    fn input_and_output_can_both_be_injected() {
+        let sink = Sink::default();
+        let mut interpreter = Interpreter::new()
+            .with_input(Cursor::new("سامي\n"))
+            .with_output(sink.clone());
+        interpreter
+            .eval("اطبع \"مرحبا \" + اقرأ(\"الاسم: \").")
+            .unwrap();
+        assert_eq!(sink.contents(), "الاسم: مرحبا سامي");
+    }
+
+    #[test]
+    fn chained_declaration_binds_every_name() {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval("متغير أ = ب = ٥.").unwrap();
+        assert_eq!(interpreter.get_variable("أ"), Some(&Value::Integer(5)));
+        assert_eq!(interpreter.get_variable("ب"), Some(&Value::Integer(5)));
+
+        interpreter.eval("أ = ب = ٧.").unwrap();
+        assert_eq!(interpreter.get_variable("أ"), Some(&Value::Integer(7)));
+        assert_eq!(interpreter.get_variable("ب"), Some(&Value::Integer(7)));
+    }
+
+    #[test]
+    fn chained_assignment_to_an_undeclared_name_changes_nothing() {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval("متغير ب = ١.").unwrap();
+        assert_eq!(
+            interpreter.eval("أ = ب = ٥."),
+            Err("Assignment to undeclared variable: أ".to_string())
+        );
+        assert_eq!(interpreter.get_variable("ب"), Some(&Value::Integer(1)));
+        assert_eq!(
+            interpreter.eval("ب = أ = ٥."),
+            Err("Assignment to undeclared variable: أ".to_string())
+        );
+        assert_eq!(interpreter.get_variable("ب"), Some(&Value::Integer(1)));
+    }
+
+    #[test]
+    fn greater_than_compares_the_right_way() {
+        assert_eq!(eval("٥ > ٣."), Ok(Value::Boolean(true)));
+        assert_eq!(eval("٣ > ٥."), Ok(Value::Boolean(false)));
+    }
+
+    #[test]
+    fn empty_programs_run_without_variables() {
+        for source in ["", " \n\t\n", "# تعليق فقط\n# وتعليق آخر"] {
+            let mut interpreter = Interpreter::new();
+            assert_eq!(interpreter.eval(source), Ok(Value::Null));
+            assert!(interpreter.variables().is_empty());
+        }
+    }
+
+    #[test]
+    fn string_index_counts_characters() {
+        assert_eq!(eval("\"مرحبا\"[٠]."), Ok(Value::String("م".to_string())));
+        assert_eq!(eval("\"مرحبا\"[٤]."), Ok(Value::String("ا".to_string())));
+        assert_eq!(eval("\"مرحبا\"[-١]."), Ok(Value::String("ا".to_string())));
+        assert_eq!(
+            eval("\"مرحبا\"[٥]."),
+            Err("Index 5 is out of range for length 5".to_string())
+        );
+    }
+
+    #[test]
+    fn if_evaluates_to_the_taken_branch() {
+        let ladder = |condition: &str| {
+            eval(&format!(
+                "لو {} ف\n  \"أول\".\n  \"نعم\".\nوإلا\n  \"لا\".\nتم",
+                condition
+            ))
+        };
+        assert_eq!(ladder("نعم"), Ok(Value::String("نعم".to_string())));
+        assert_eq!(ladder("لا"), Ok(Value::String("لا".to_string())));
+        assert_eq!(eval("لو لا ف \"نعم\". تم"), Ok(Value::Null));
+        assert_eq!(eval("لو نعم ف تم"), Ok(Value::Null));
+        assert_eq!(eval("لو لا ف \"نعم\". وإلا تم"), Ok(Value::Null));
+    }
+
+    #[test]
+    fn null_equals_only_null() {
+        assert_eq!(eval("فارغ == فارغ."), Ok(Value::Boolean(true)));
+        assert_eq!(eval("فارغ != فارغ."), Ok(Value::Boolean(false)));
+        assert_eq!(eval("فارغ == ٣."), Ok(Value::Boolean(false)));
+        assert_eq!(eval("فارغ != ٣."), Ok(Value::Boolean(true)));
+    }
+
+    #[test]
+    fn null_cannot_be_ordered() {
+        assert_eq!(
+            eval("فارغ < ١."),
+            Err("Cannot apply < between Null and Integer at line 1, col 6".to_string())
+        );
+        assert_eq!(
+            eval("فارغ < فارغ."),
+            Err("Unknown operator for null: LT at line 1, col 6".to_string())
+        );
+    }
+
+    #[test]
+    fn two_character_comparisons_evaluate() {
+        assert_eq!(eval("٥ >= ٥."), Ok(Value::Boolean(true)));
+        assert_eq!(eval("٣ != ٤."), Ok(Value::Boolean(true)));
+        let mut interpreter = Interpreter::new();
+        interpreter.eval("متغير س = ٠.\nس = ١.").unwrap();
+        assert_eq!(interpreter.get_variable("س"), Some(&Value::Integer(1)));
+    }
+
+    #[test]
+    fn strings_concatenate() {
+        assert_eq!(
+            eval("\"مرحبا\" + \" عالم\"."),
+            Ok(Value::String("مرحبا عالم".to_string()))
+        );
+    }
+
+    #[test]
+    fn strings_cannot_be_subtracted() {
+        assert_eq!(
+            eval("\"أ\" - \"ب\"."),
+            Err("Unknown operator for strings: Minus at line 1, col 5".to_string())
+        );
+    }
+
+    #[test]
+    fn reassigning_a_declared_variable() {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval("متغير س = ١.\nس = س + ٤.").unwrap();
+        assert_eq!(interpreter.get_variable("س"), Some(&Value::Integer(5)));
+    }
+
+    #[test]
+    fn assigning_an_undeclared_variable_fails() {
+        assert_eq!(
+            eval("س = ٥."),
+            Err("Assignment to undeclared variable: س".to_string())
+        );
+    }
+
+    #[test]
+    fn while_loop_counts_down() {
+        let sink = Sink::default();
+        let mut interpreter = Interpreter::new().with_output(sink.clone());
+        interpreter
+            .eval("متغير س = ٥.\nبينما س > ٠ ف\n  اطبع س.\n  س = س - ١.\nتم")
+            .unwrap();
+        assert_eq!(sink.contents(), "54321");
+        assert_eq!(interpreter.get_variable("س"), Some(&Value::Integer(0)));
+    }
+
+    #[test]
+    fn while_condition_must_be_boolean() {
+        assert_eq!(
+            eval("بينما ١ ف تم"),
+            Err("Condition must evaluate to a boolean".to_string())
+        );
+    }
+
+    #[test]
+    fn logical_operators() {
+        assert_eq!(eval("نعم و لا."), Ok(Value::Boolean(false)));
+        assert_eq!(eval("لا أو نعم."), Ok(Value::Boolean(true)));
+        assert_eq!(eval("١ < ٢ و ٣ > ٢."), Ok(Value::Boolean(true)));
+    }
+
+    #[test]
+    fn logical_operators_short_circuit() {
+        assert_eq!(eval("لا و مجهول."), Ok(Value::Boolean(false)));
+        assert_eq!(eval("نعم أو مجهول."), Ok(Value::Boolean(true)));
+        assert_eq!(
+            eval("نعم و مجهول."),
+            Err("Undefined variable: مجهول at line 1, col 7".to_string())
+        );
+    }
+
+    #[test]
+    fn statement_after_a_comment_runs() {
+        assert_eq!(output("# تعليق\nاطبع \"#\"."), "#");
+    }
+
+    #[test]
+    fn if_as_the_value_of_a_declaration() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .eval("متغير س = ٧.\nمتغير وصف = لو س > ٥ ف \"كبير\". وإلا \"صغير\". تم")
+            .unwrap();
+        assert_eq!(
+            interpreter.get_variable("وصف"),
+            Some(&Value::String("كبير".to_string()))
+        );
+    }
+
+    #[test]
+    fn unary_minus() {
+        assert_eq!(eval("-٥ + ٣."), Ok(Value::Integer(-2)));
+        assert_eq!(eval("-(٢ * ٣)."), Ok(Value::Integer(-6)));
+        assert_eq!(eval("-٢٫٥."), Ok(Value::Number(-2.5)));
+        assert_eq!(
+            eval("-\"نص\"."),
+            Err("Unknown unary operator Minus for String(\"نص\")".to_string())
+        );
+    }
+
+    #[test]
+    fn logical_not() {
+        assert_eq!(eval("ليس لا."), Ok(Value::Boolean(true)));
+        assert_eq!(eval("ليس (٣ > ٥)."), Ok(Value::Boolean(true)));
+        assert_eq!(
+            eval("ليس ٥."),
+            Err("Logical not requires a boolean, got Integer(5)".to_string())
+        );
+    }
+
+    #[test]
+    fn integers_stay_integers_until_mixed_with_floats() {
+        assert!(matches!(eval("٥ + ٣."), Ok(Value::Integer(8))));
+        assert!(matches!(eval("٥ * ٣ - ١."), Ok(Value::Integer(14))));
+        assert!(matches!(eval("٥ + ٠٫٥."), Ok(Value::Number(n)) if n == 5.5));
+        assert!(matches!(eval("٢٫٥ * ٢."), Ok(Value::Number(n)) if n == 5.0));
+        assert_eq!(
+            eval("٥ / ٠."),
+            Err("Division by zero at line 1, col 3".to_string())
+        );
+    }
+
+    #[test]
+    fn modulo() {
+        assert_eq!(eval("١٠ ٪ ٣."), Ok(Value::Integer(1)));
+        assert_eq!(eval("٧٫٥ ٪ ٢."), Ok(Value::Number(1.5)));
+        assert_eq!(
+            eval("١٠ ٪ ٠."),
+            Err("Modulo by zero at line 1, col 4".to_string())
+        );
+    }
+
+    #[test]
+    fn else_if_ladder_picks_the_middle_branch() {
+        let source = "متغير س = ٥.
+لو س < ٠ ف
+  \"سالب\".
+وإلا لو س < ١٠ ف
+  \"صغير\".
+وإلا
+  \"كبير\".
+تم";
+        assert_eq!(eval(source), Ok(Value::String("صغير".to_string())));
+    }
+
+    #[test]
+    fn if_on_the_line_after_else_is_nested_in_the_else_block() {
+        let source = "لو لا ف ١. وإلا
+  لو نعم ف ٢. تم
+  ٣.
+تم";
+        assert_eq!(eval(source), Ok(Value::Integer(3)));
+    }
+
+    #[test]
+    fn return_ends_the_function_early() {
+        let sink = Sink::default();
+        let mut interpreter = Interpreter::new().with_output(sink.clone());
+        let source = "دالة د() ف\n  أرجع ١.\n  اطبع \"لم يصل\".\nتم\nد().";
+        assert_eq!(interpreter.eval(source), Ok(Value::Integer(1)));
+        assert_eq!(sink.contents(), "");
+    }
+
+    #[test]
+    fn return_from_nested_blocks() {
+        let source = "دالة أول_زوجي(أعداد) ف
+  لكل ع في أعداد ف
+    لو ع ٪ ٢ == ٠ ف
+      أرجع ع.
+    تم
+  تم
+  أرجع فارغ.
+تم
+أول_زوجي([١، ٣، ٤، ٦]).";
+        assert_eq!(eval(source), Ok(Value::Integer(4)));
+    }
+
+    #[test]
+    fn string_comparisons() {
+        assert_eq!(eval("\"أ\" < \"ب\"."), Ok(Value::Boolean(true)));
+        assert_eq!(eval("\"نص\" == \"نص\"."), Ok(Value::Boolean(true)));
+    }
+
+    #[test]
+    fn variable_declared_as_null() {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval("متغير س = فارغ.").unwrap();
+        assert_eq!(interpreter.get_variable("س"), Some(&Value::Null));
+        assert_eq!(interpreter.eval("س == فارغ."), Ok(Value::Boolean(true)));
+        assert_eq!(interpreter.eval("س != ٠."), Ok(Value::Boolean(true)));
+        assert_eq!(output("اطبع_سطر(فارغ)."), "فارغ\n");
+    }
+
+    #[test]
+    fn block_locals_end_with_their_block() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .eval("لو نعم ف\n  متغير داخلي = ١.\nتم")
+            .unwrap();
+        assert_eq!(interpreter.get_variable("داخلي"), None);
+        let error = interpreter.eval("داخلي.").unwrap_err();
+        assert!(error.starts_with("Undefined variable: داخلي"), "{}", error);
+    }
+
+    #[test]
+    fn blocks_read_and_update_outer_variables() {
+        let mut interpreter = Interpreter::new();
+        let source = "متغير خارجي = ٢.\nلو نعم ف\n  متغير نسخة = خارجي.\n  خارجي = نسخة * ٣.\nتم";
+        interpreter.eval(source).unwrap();
+        assert_eq!(interpreter.get_variable("خارجي"), Some(&Value::Integer(6)));
+    }
+
+    #[test]
+    fn display_scalars() {
+        assert_eq!(Value::Integer(5).to_string(), "5");
+        assert_eq!(Value::Number(5.0).to_string(), "5");
+        assert_eq!(Value::Number(2.5).to_string(), "2.5");
+        assert_eq!(Value::String("نص".to_string()).to_string(), "نص");
+        assert_eq!(Value::Boolean(true).to_string(), "نعم");
+        assert_eq!(Value::Boolean(false).to_string(), "لا");
+        assert_eq!(Value::Null.to_string(), "فارغ");
+    }
+
+    #[test]
+    fn display_collections() {
+        let array = Value::Array(vec![
+            Value::Integer(1),
+            Value::String("أ".to_string()),
+            Value::Null,
+        ]);
+        assert_eq!(array.to_string(), "[1، أ، فارغ]");
+        let mut fields = IndexMap::new();
+        fields.insert("س".to_string(), Value::Integer(1));
+        fields.insert("ص".to_string(), Value::Boolean(false));
+        assert_eq!(Value::Record(fields).to_string(), "صف(س = 1، ص = لا)");
+        let mut entries = IndexMap::new();
+        entries.insert(MapKey::String("أ".to_string()), Value::Integer(1));
+        entries.insert(MapKey::Integer(2), Value::Number(0.5));
+        assert_eq!(Value::Map(entries).to_string(), "خريطة(أ: 1، 2: 0.5)");
+    }
+
+    #[test]
+    fn constants_are_read_but_not_reassigned() {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval("ثابت ط = ٣.").unwrap();
+        assert_eq!(interpreter.eval("ط * ٢."), Ok(Value::Integer(6)));
+        let error = interpreter.eval("ط = ٤.").unwrap_err();
+        assert!(
+            error.starts_with("Cannot reassign constant: ط"),
+            "{}",
+            error
+        );
+        assert_eq!(interpreter.get_variable("ط"), Some(&Value::Integer(3)));
+    }
+
+    #[test]
+    fn power_is_right_associative() {
+        assert_eq!(eval("٢ ^ ٣."), Ok(Value::Integer(8)));
+        assert_eq!(eval("٢ ^ ٢ ^ ٣."), Ok(Value::Integer(256)));
+        assert_eq!(eval("٢ * ٣ ^ ٢."), Ok(Value::Integer(18)));
+    }
+
+    #[test]
+    fn for_each_sums_an_array() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .eval("متغير مجموع = ٠.\nلكل ع في [١، ٢، ٣] ف\n  مجموع = مجموع + ع.\nتم")
+            .unwrap();
+        assert_eq!(interpreter.get_variable("مجموع"), Some(&Value::Integer(6)));
+        assert_eq!(interpreter.get_variable("ع"), None);
+    }
+
+    #[test]
+    fn for_each_rejects_non_arrays() {
+        let error = eval("لكل ع في ٥ ف تم").unwrap_err();
+        assert!(
+            error.starts_with("Cannot iterate over Integer(5)"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn booleans_compare_for_equality() {
+        assert_eq!(eval("نعم == لا."), Ok(Value::Boolean(false)));
+        assert_eq!(eval("لا != نعم."), Ok(Value::Boolean(true)));
+        assert_eq!(eval("نعم == نعم."), Ok(Value::Boolean(true)));
+    }
+
+    #[test]
+    fn numbers_join_strings_in_either_order() {
+        assert_eq!(
+            eval("\"العدد: \" + ٥."),
+            Ok(Value::String("العدد: 5".to_string()))
+        );
+        assert_eq!(
+            eval("٢٫٠ + \" كغ\"."),
+            Ok(Value::String("2 كغ".to_string()))
+        );
+        let error = eval("\"أ\" - ١.").unwrap_err();
+        assert!(
+            error.starts_with("Cannot apply - between String and Integer"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn eval_returns_the_last_value() {
+        assert_eq!(eval("٢ + ٣ * ٤."), Ok(Value::Integer(14)));
+        assert_eq!(eval("متغير س = ١.\nس + ١."), Ok(Value::Integer(2)));
+    }
+
+    #[test]
+    fn statements_follow_a_completed_if() {
+        let source = "لو لا ف\n  اطبع_سطر(\"أ\").\nوإلا\n  اطبع_سطر(\"ب\").\nتم\nاطبع_سطر(\"ج\").";
+        assert_eq!(output(source), "ب\nج\n");
+        assert_eq!(output("لو نعم ف اطبع_سطر(١). تم اطبع_سطر(٢)."), "1\n2\n");
+    }
+
+    #[test]
+    fn variables_keep_declaration_order() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .eval("متغير ي = ١.\nمتغير أ = \"ب\".\nمتغير م = نعم.\nي = ٢.")
+            .unwrap();
+        let names: Vec<&str> = interpreter.variables().keys().map(String::as_str).collect();
+        assert_eq!(names, ["ي", "أ", "م"]);
+        assert_eq!(interpreter.snapshot(), "ي = 2\nأ = ب\nم = نعم\n");
+    }
+
+    #[test]
+    fn conditional_expression_as_a_declared_value() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .eval("متغير س = ٥.\nمتغير وصف = إذا س > ٣ فإن \"كبير\" وإلا \"صغير\".")
+            .unwrap();
+        assert_eq!(
+            interpreter.get_variable("وصف"),
+            Some(&Value::String("كبير".to_string()))
+        );
+    }
+
+    #[test]
+    fn conditional_expression_skips_the_other_branch() {
+        assert_eq!(eval("إذا نعم فإن ١ وإلا ١ / ٠."), Ok(Value::Integer(1)));
+        assert_eq!(eval("إذا لا فإن خطأ(\"لا\") وإلا ٢."), Ok(Value::Integer(2)));
+    }
+
+    #[test]
+    fn array_index_from_either_end() {
+        assert_eq!(eval("[١٠، ٢٠، ٣٠][٠]."), Ok(Value::Integer(10)));
+        assert_eq!(eval("[١٠، ٢٠، ٣٠][-١]."), Ok(Value::Integer(30)));
+        assert_eq!(eval("[١٠، ٢٠، ٣٠][٢]."), Ok(Value::Integer(30)));
+    }
+
+    #[test]
+    fn array_index_out_of_range() {
+        for index in ["٣", "-٤"] {
+            let error = eval(&format!("[١٠، ٢٠، ٣٠][{}].", index)).unwrap_err();
+            assert!(error.contains("is out of range for length 3"), "{}", error);
         }
-        Err(e) => println!("Error: {}", e),
+        let error = eval("[١٠][٠٫٥].").unwrap_err();
+        assert!(
+            error.starts_with("Index must be an integer, got Number(0.5)"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn repeat_runs_the_body_count_times() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .eval("متغير حروف = \"\".\nكرر ٣ مرة ف\n  حروف = حروف + \"أ\".\nتم")
+            .unwrap();
+        assert_eq!(
+            interpreter.get_variable("حروف"),
+            Some(&Value::String("أأأ".to_string()))
+        );
+    }
+
+    #[test]
+    fn repeat_rejects_negative_counts() {
+        let error = eval("كرر -١ مرة ف تم").unwrap_err();
+        assert!(
+            error.starts_with("Repeat count must be a non-negative integer, got Integer(-1)"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn undefined_variable_names_its_line() {
+        assert_eq!(
+            eval("متغير س = ١.\n\nلو نعم ف\n  س = ص + ١.\nتم"),
+            Err("Undefined variable: ص at line 4, col 7".to_string())
+        );
+    }
+
+    #[test]
+    fn builtin_error_names_its_line() {
+        assert_eq!(
+            eval("متغير س = -٤.\nلو نعم ف\n  اطبع جذر(س).\nتم"),
+            Err("Cannot take the square root of a negative number at line 3, col 8".to_string())
+        );
+        assert_eq!(eval("\nخطأ(\"سالب\")."), Err("سالب".to_string()));
+    }
+
+    #[test]
+    fn declared_variable_starts_null_until_assigned() {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval("متغير س.").unwrap();
+        assert_eq!(interpreter.get_variable("س"), Some(&Value::Null));
+        assert_eq!(interpreter.eval("س == فارغ."), Ok(Value::Boolean(true)));
+        interpreter.eval("س = \"قيمة\".").unwrap();
+        assert_eq!(
+            interpreter.get_variable("س"),
+            Some(&Value::String("قيمة".to_string()))
+        );
+    }
+
+    #[test]
+    fn ordering_mismatched_types_names_both() {
+        assert_eq!(
+            eval("٥ > \"نص\"."),
+            Err("Cannot apply > between Integer and String at line 1, col 3".to_string())
+        );
+        assert_eq!(
+            eval("[١] <= ٢٫٥."),
+            Err("Cannot apply <= between Array and Number at line 1, col 5".to_string())
+        );
+    }
+
+    #[test]
+    fn print_without_and_with_a_newline() {
+        assert_eq!(output("اطبع \"أ\".\nاطبع ١."), "أ1");
+        assert_eq!(output("اطبع_سطر \"أ\".\nاطبع_سطر ١."), "أ\n1\n");
+        assert_eq!(output("اطبع \"س = \".\nاطبع_سطر ٢."), "س = 2\n");
+    }
+
+    #[test]
+    fn modulo_of_booleans_and_strings_is_explained() {
+        assert_eq!(
+            eval("نعم ٪ لا."),
+            Err(
+                "Modulo (٪) is only defined for numbers, not booleans at line 1, col 5".to_string()
+            )
+        );
+        assert_eq!(
+            eval("\"أ\" ٪ \"ب\"."),
+            Err("Modulo (٪) is only defined for numbers, not strings at line 1, col 5".to_string())
+        );
+    }
+
+    #[test]
+    fn iteration_cap_stops_a_runaway_loop() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_max_iterations(Some(10));
+        let error = interpreter
+            .eval("متغير ن = ٠.\nبينما نعم ف\n  ن = ن + ١.\nتم")
+            .unwrap_err();
+        assert!(
+            error.starts_with("Loop exceeded the maximum of 10 iterations"),
+            "{}",
+            error
+        );
+        assert_eq!(interpreter.get_variable("ن"), Some(&Value::Integer(10)));
+    }
+
+    #[test]
+    fn iteration_cap_allows_loops_within_it() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_max_iterations(Some(10));
+        assert!(interpreter.eval("كرر ١٠ مرة ف تم").is_ok());
+        assert!(interpreter.eval("كرر ١١ مرة ف تم").is_err());
+    }
+
+    #[test]
+    fn arrays_concatenate() {
+        assert_eq!(
+            eval("[١، ٢] + [٣]."),
+            Ok(Value::Array(vec![
+                Value::Integer(1),
+                Value::Integer(2),
+                Value::Integer(3)
+            ]))
+        );
+        assert_eq!(eval("[] + []."), Ok(Value::Array(vec![])));
+    }
+
+    #[test]
+    fn arrays_cannot_be_subtracted() {
+        assert_eq!(
+            eval("[١، ٢] - [٢]."),
+            Err("Unknown operator for arrays: Minus at line 1, col 8".to_string())
+        );
+    }
+
+    #[test]
+    fn record_fields_are_read_by_name() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .eval("متغير شخص = صف(اسم = \"سارة\"، عمر = ٣٠).")
+            .unwrap();
+        assert_eq!(
+            interpreter.eval("شخص.اسم."),
+            Ok(Value::String("سارة".to_string()))
+        );
+        assert_eq!(interpreter.eval("شخص.عمر + ١."), Ok(Value::Integer(31)));
+        assert_eq!(
+            interpreter.eval("شخص.طول."),
+            Err("Record has no field named طول".to_string())
+        );
+    }
+
+    #[test]
+    fn truthy_conditions_follow_is_truthy() {
+        let cases = [
+            ("٠", false),
+            ("٠٫٠", false),
+            ("\"\"", false),
+            ("فارغ", false),
+            ("لا", false),
+            ("١", true),
+            ("-٢٫٥", true),
+            ("\"أ\"", true),
+            ("نعم", true),
+            ("[]", true),
+            ("صف(س = ٠)", true),
+        ];
+        for (value, truthy) in cases {
+            let mut interpreter = Interpreter::new();
+            interpreter.set_truthy_conditions(true);
+            let source = format!("لو {} ف نعم. وإلا لا. تم", value);
+            assert_eq!(
+                interpreter.eval(&source),
+                Ok(Value::Boolean(truthy)),
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn conditions_must_be_booleans_by_default() {
+        let error = eval("لو ١ ف نعم. تم").unwrap_err();
+        assert!(
+            error.starts_with("Condition must evaluate to a boolean"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn map_lookup_and_insertion() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .eval("متغير أعمار = خريطة(\"سارة\": ٣٠، ٧: \"سبعة\").")
+            .unwrap();
+        assert_eq!(interpreter.eval("أعمار[\"سارة\"]."), Ok(Value::Integer(30)));
+        assert_eq!(
+            interpreter.eval("أعمار[٧]."),
+            Ok(Value::String("سبعة".to_string()))
+        );
+        interpreter.eval("أعمار[\"علي\"] = ٢٥.").unwrap();
+        assert_eq!(interpreter.eval("أعمار[\"علي\"]."), Ok(Value::Integer(25)));
+    }
+
+    #[test]
+    fn map_lookup_of_an_absent_key() {
+        let error = eval("خريطة(\"أ\": ١)[\"ب\"].").unwrap_err();
+        assert!(error.starts_with("Map has no key ب"), "{}", error);
+    }
+
+    #[test]
+    fn floats_print_trimmed_by_default() {
+        assert_eq!(output("اطبع_سطر ٢٥ / ٣."), "8.333333333333334\n");
+        assert_eq!(output("اطبع_سطر ٢٫٥٠."), "2.5\n");
+        assert_eq!(output("اطبع_سطر ٣٫٠."), "3\n");
+    }
+
+    #[test]
+    fn float_precision_fixes_the_decimals() {
+        let sink = Sink::default();
+        let mut interpreter = Interpreter::new().with_output(sink.clone());
+        interpreter.set_float_precision(Some(2));
+        interpreter
+            .eval("اطبع_سطر ٢٥ / ٣.\nاطبع_سطر [٠٫٥، ٧].\nاطبع_سطر ٧.")
+            .unwrap();
+        assert_eq!(sink.contents(), "8.33\n[0.50، 7]\n7\n");
+    }
+
+    #[test]
+    fn dots_and_line_ends_both_end_statements() {
+        let with_dots = "متغير س = ١.\nمتغير ص = س +\n  ٢.\nلو ص > س ف\n  س = ص.\nتم";
+        let with_lines = "متغير س = ١\nمتغير ص = س +\n  ٢\nلو ص > س ف\n  س = ص\nتم";
+        for source in [with_dots, with_lines] {
+            let mut interpreter = Interpreter::new();
+            interpreter.eval(source).unwrap();
+            assert_eq!(interpreter.snapshot(), "س = 3\nص = 3\n", "{}", source);
+        }
+    }
+
+    #[test]
+    fn values_of_different_types_are_unequal() {
+        assert_eq!(eval("٥ == \"٥\"."), Ok(Value::Boolean(false)));
+        assert_eq!(eval("٥ != \"٥\"."), Ok(Value::Boolean(true)));
+        assert_eq!(eval("لا == ٠."), Ok(Value::Boolean(false)));
+        assert_eq!(eval("[١] != ١."), Ok(Value::Boolean(true)));
+    }
+
+    #[test]
+    fn snapshot_lists_name_equals_value() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .eval("متغير اسم = \"سارة\".\nمتغير قيم = [١، ٢٫٥].\nمتغير فراغ.\nثابت صح = نعم.")
+            .unwrap();
+        assert_eq!(
+            interpreter.snapshot(),
+            "اسم = سارة\nقيم = [1، 2.5]\nفراغ = فارغ\nصح = نعم\n"
+        );
+        assert_eq!(Interpreter::new().snapshot(), "");
     }
 }
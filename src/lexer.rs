@@ -1,15 +1,37 @@
+use std::fmt;
+use std::iter::Peekable;
 use std::str::Chars;
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "value"))]
 pub enum Token {
     VariableKeyword,
+    ConstantKeyword,
     IfKeyword,
     ElseKeyword,
     ThenKeyword,
+    ConditionalKeyword,
+    ConditionalThenKeyword,
+    WhileKeyword,
+    EndKeyword,
+    FunctionKeyword,
+    ReturnKeyword,
+    ForKeyword,
+    InKeyword,
+    RepeatKeyword,
+    TimesKeyword,
+    PrintKeyword,
+    PrintLineKeyword,
+    RecordKeyword,
+    MapKeyword,
     True,
     False,
+    Null,
 
     Identifier(String),
+    Integer(i64),
     Number(f64),
     String(String),
 
@@ -17,6 +39,8 @@ pub enum Token {
     Minus,
     Multiply,
     Divide,
+    Modulo,
+    Power,
 
     LT,
     GT,
@@ -25,137 +49,801 @@ pub enum Token {
     EQ,
     NEQ,
 
+    And,
+    Or,
+    Not,
+
     LeftParen,
     RightParen,
+    LeftBracket,
+    RightBracket,
+    Comma,
+    Colon,
     Equals,
 
     Dot,
+    Member,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Span {
+    pub line: usize,
+    pub col: usize,
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, col {}", self.line, self.col)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
+    // How a keyword was spelled, since some keywords (عرف and متغير) share a token
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub keyword: Option<&'static str>,
 }
 
+static KEYWORDS: &[(&str, Token)] = &[
+    ("عرف", Token::VariableKeyword),
+    ("متغير", Token::VariableKeyword),
+    ("ثابت", Token::ConstantKeyword),
+    ("لو", Token::IfKeyword),
+    ("ف", Token::ThenKeyword),
+    ("وإلا", Token::ElseKeyword),
+    ("إذا", Token::ConditionalKeyword),
+    ("فإن", Token::ConditionalThenKeyword),
+    ("اطبع", Token::PrintKeyword),
+    ("اطبع_سطر", Token::PrintLineKeyword),
+    ("صف", Token::RecordKeyword),
+    ("خريطة", Token::MapKeyword),
+    ("بينما", Token::WhileKeyword),
+    ("تم", Token::EndKeyword),
+    ("دالة", Token::FunctionKeyword),
+    ("أرجع", Token::ReturnKeyword),
+    ("لكل", Token::ForKeyword),
+    ("في", Token::InKeyword),
+    ("كرر", Token::RepeatKeyword),
+    ("مرة", Token::TimesKeyword),
+    ("و", Token::And),
+    ("أو", Token::Or),
+    ("ليس", Token::Not),
+    ("نعم", Token::True),
+    ("لا", Token::False),
+    ("فارغ", Token::Null),
+];
+
 struct Lexer<'a> {
-    chars: Chars<'a>,
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+    col: usize,
+    newline_terminates: bool,
+    // Whether the last token could end a statement, and how deeply nested in ( ) or [ ] we are
+    statement_open: bool,
+    depth: usize,
+    // Whether the last token was a name, `)` or `]`, which a `.` may follow as field access
+    accessible: bool,
 }
 
 impl<'a> Lexer<'a> {
-    fn new(input: &'a str) -> Self {
+    fn new(input: &'a str, newline_terminates: bool) -> Self {
         Lexer {
-            chars: input.chars(),
+            chars: input.chars().peekable(),
+            line: 1,
+            col: 1,
+            newline_terminates,
+            statement_open: false,
+            depth: 0,
+            accessible: false,
+        }
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let ch = self.chars.next()?;
+        if ch == '\n' {
+            self.line += 1;
+            self.col = 1;
+        } else {
+            self.col += 1;
         }
+        Some(ch)
     }
 
-    fn next_token(&mut self) -> Option<Token> {
+    fn next_token(&mut self) -> Result<Option<SpannedToken>, String> {
+        let (line, col) = (self.line, self.col);
         self.skip_whitespace();
+        let adjacent = (self.line, self.col) == (line, col);
 
-        match self.chars.next() {
+        let span = Span {
+            line: self.line,
+            col: self.col,
+        };
+        // A line break (or the end of input) ends a statement left without its `.`, which
+        // then sits just after the statement's last token
+        let at_line_end = self.line > line || self.chars.peek().is_none();
+        if self.newline_terminates && self.statement_open && self.depth == 0 && at_line_end {
+            self.statement_open = false;
+            return Ok(Some(SpannedToken {
+                token: Token::Dot,
+                span: Span { line, col },
+                keyword: None,
+            }));
+        }
+        let mut keyword = None;
+        let token = match self.advance() {
             Some(ch) => match ch {
                 '+' => Some(Token::Plus),
                 '-' => Some(Token::Minus),
                 '*' => Some(Token::Multiply),
                 '/' => Some(Token::Divide),
-                '<' => Some(Token::LT),
-                '>' => Some(Token::LT),
-                // '<=' => Some(Token::LTE),
-                // '>=' => Some(Token::GTE),
-                // '==' => Some(Token::EQ),
-                // '!=' => Some(Token::NEQ),
+                '٪' => Some(Token::Modulo),
+                '^' => Some(Token::Power),
+                '<' => Some(self.read_operator(Token::LT, Token::LTE)),
+                '>' => Some(self.read_operator(Token::GT, Token::GTE)),
+                '=' => Some(self.read_operator(Token::Equals, Token::EQ)),
+                '!' => {
+                    if self.chars.peek() == Some(&'=') {
+                        self.advance();
+                        Some(Token::NEQ)
+                    } else {
+                        return Err(format!(
+                            "Unexpected character '!' at {}, did you mean '!='?",
+                            span
+                        ));
+                    }
+                }
                 '(' => Some(Token::LeftParen),
                 ')' => Some(Token::RightParen),
-                '=' => Some(Token::Equals),
+                '[' => Some(Token::LeftBracket),
+                ']' => Some(Token::RightBracket),
+                '،' => Some(Token::Comma),
+                ':' => Some(Token::Colon),
+                // `س.اسم` with nothing in between reads a field, unless the word is a keyword as
+                // in `س.اطبع`; any other `.` ends a statement
+                '.' if adjacent && self.accessible && self.next_word_is_name() => {
+                    Some(Token::Member)
+                }
                 '.' => Some(Token::Dot),
-                '"' => Some(self.read_string()),
-                '٠'..='٩' => Some(self.read_number(ch)),
-                'ا'..='ي' | 'آ' | 'أ' | 'إ' => Some(self.read_identifier_or_keyword(ch)),
-                _ => None, // Unrecognized character
+                '"' => Some(self.read_string(span)?),
+                '٠'..='٩' | '0'..='9' => Some(self.read_number(ch, span)?),
+                ch if is_identifier_start(ch) => {
+                    let (token, spelling) = self.read_identifier_or_keyword(ch);
+                    keyword = spelling;
+                    Some(token)
+                }
+                _ => {
+                    return Err(format!("Unrecognized character '{}' at {}", ch, span));
+                }
             },
             None => None, // End of input
+        };
+        if let Some(token) = &token {
+            match token {
+                Token::LeftParen | Token::LeftBracket => self.depth += 1,
+                Token::RightParen | Token::RightBracket => {
+                    self.depth = self.depth.saturating_sub(1)
+                }
+                _ => {}
+            }
+            self.accessible = matches!(
+                token,
+                Token::Identifier(_) | Token::RightParen | Token::RightBracket
+            );
+            self.statement_open = matches!(
+                token,
+                Token::Identifier(_)
+                    | Token::Integer(_)
+                    | Token::Number(_)
+                    | Token::String(_)
+                    | Token::True
+                    | Token::False
+                    | Token::Null
+                    | Token::RightParen
+                    | Token::RightBracket
+            );
+        }
+        Ok(token.map(|token| SpannedToken {
+            token,
+            span,
+            keyword,
+        }))
+    }
+
+    fn read_operator(&mut self, single: Token, with_equals: Token) -> Token {
+        if self.chars.peek() == Some(&'=') {
+            self.advance();
+            with_equals
+        } else {
+            single
         }
     }
 
     fn skip_whitespace(&mut self) {
-        while let Some(ch) = self.chars.clone().next() {
-            if !ch.is_whitespace() {
+        while let Some(ch) = self.chars.peek().copied() {
+            if ch == '#' {
+                self.skip_comment();
+            } else if ch.is_whitespace() {
+                self.advance();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        while let Some(ch) = self.advance() {
+            if ch == '\n' {
                 break;
             }
-            self.chars.next();
         }
     }
 
-    fn read_number(&mut self, first_digit: char) -> Token {
+    // Digits may be Eastern Arabic (٠-٩) or Western (0-9), mixed freely within a literal
+    fn read_number(&mut self, first_digit: char, span: Span) -> Result<Token, String> {
+        if digit_value(first_digit) == Some(0) {
+            let radix = match self.chars.peek() {
+                Some('خ' | 'x') => Some(16),
+                Some('ث' | 'b') => Some(2),
+                _ => None,
+            };
+            if let Some(radix) = radix {
+                self.advance();
+                return self.read_radix_integer(radix, span);
+            }
+        }
         let mut number = first_digit.to_string();
-        while let Some(ch) = self.chars.clone().next() {
-            if !('٠'..='٩').contains(&ch) && ch != ',' {
+        let mut has_separator = false;
+        while let Some(ch) = self.chars.peek().copied() {
+            match ch {
+                '٠'..='٩' | '0'..='9' => {}
+                ',' | '٬' if !has_separator => {}
+                '٫' if has_separator => {
+                    return Err(format!(
+                        "Number has more than one decimal separator at {}",
+                        span
+                    ));
+                }
+                '٫' => has_separator = true,
+                _ => break,
+            }
+            number.push(self.advance().unwrap());
+        }
+        if number.ends_with('٫') {
+            return Err(format!("Number ends with a decimal separator at {}", span));
+        }
+        // Grouping separators (`,` or `٬`) only sit between digits, unlike the `،` element separator
+        let mut chars = number.chars().peekable();
+        while let Some(ch) = chars.next() {
+            if is_grouping_separator(ch) && chars.peek().is_none_or(|c| digit_value(*c).is_none()) {
+                return Err(format!(
+                    "Grouping separator must be followed by a digit at {}",
+                    span
+                ));
+            }
+        }
+        if has_separator {
+            arabic_numeral_to_float(&number)
+                .map(Token::Number)
+                .map_err(|e| format!("{} at {}", e, span))
+        } else {
+            arabic_numeral_to_integer(&number)
+                .map(Token::Integer)
+                .ok_or_else(|| format!("Integer literal is too large at {}", span))
+        }
+    }
+
+    // Reads the digits after a `٠خ`/`0x` (hex) or `٠ث`/`0b` (binary) prefix
+    fn read_radix_integer(&mut self, radix: u32, span: Span) -> Result<Token, String> {
+        let mut digits = String::new();
+        while let Some(ch) = self.chars.peek().copied() {
+            if !ch.is_alphanumeric() {
                 break;
             }
-            number.push(self.chars.next().unwrap());
+            digits.push(self.advance().unwrap());
+        }
+        if digits.is_empty() {
+            return Err(format!(
+                "Missing digits after base-{} prefix at {}",
+                radix, span
+            ));
         }
-        Token::Number(arabic_numeral_to_float(&number))
+        digits
+            .chars()
+            .try_fold(0i64, |acc, c| {
+                let digit = digit_value(c)
+                    .or_else(|| c.to_digit(16))
+                    .filter(|d| *d < radix)
+                    .ok_or_else(|| {
+                        format!(
+                            "Invalid digit '{}' in base-{} literal at {}",
+                            c, radix, span
+                        )
+                    })?;
+                acc.checked_mul(radix as i64)
+                    .and_then(|acc| acc.checked_add(digit as i64))
+                    .ok_or_else(|| format!("Integer literal is too large at {}", span))
+            })
+            .map(Token::Integer)
     }
 
-    fn read_string(&mut self) -> Token {
+    fn read_string(&mut self, span: Span) -> Result<Token, String> {
         let mut string = String::new();
         let mut escaped = false;
 
-        while let Some(ch) = self.chars.next() {
+        loop {
+            let ch = match self.advance() {
+                Some(ch) => ch,
+                None => return Err(format!("Unterminated string starting at {}", span)),
+            };
             match (ch, escaped) {
                 ('"', false) => break,
                 ('\\', false) => escaped = true,
                 (ch, true) => {
-                    string.push(ch);
+                    match ch {
+                        'n' => string.push('\n'),
+                        't' => string.push('\t'),
+                        '\\' | '"' => string.push(ch),
+                        _ => {
+                            // Unknown escapes are kept as written
+                            string.push('\\');
+                            string.push(ch);
+                        }
+                    }
                     escaped = false;
                 }
                 (ch, false) => string.push(ch),
             }
         }
-        Token::String(string)
+        Ok(Token::String(string))
     }
 
-    fn read_identifier_or_keyword(&mut self, first_char: char) -> Token {
+    fn next_word_is_name(&self) -> bool {
+        let word: String = self
+            .chars
+            .clone()
+            .take_while(|c| is_identifier_char(*c))
+            .collect();
+        word.starts_with(is_identifier_start)
+            && !KEYWORDS.iter().any(|(keyword, _)| *keyword == word)
+    }
+
+    fn read_identifier_or_keyword(&mut self, first_char: char) -> (Token, Option<&'static str>) {
         let mut identifier = first_char.to_string();
-        while let Some(ch) = self.chars.clone().next() {
-            if !('ا'..='ي').contains(&ch) && !['آ', 'أ', 'إ', 'ة', 'ى'].contains(&ch) {
+        while let Some(ch) = self.chars.peek().copied() {
+            if !is_identifier_char(ch) {
                 break;
             }
-            identifier.push(self.chars.next().unwrap());
-        }
-
-        match identifier.as_str() {
-            "عرف" => Token::VariableKeyword,
-            "لو" => Token::IfKeyword,
-            "ف" => Token::ThenKeyword,
-            "وإلا" => Token::ElseKeyword,
-            "نعم" => Token::True,
-            "لا" => Token::False,
-            _ => Token::Identifier(identifier),
-        }
-    }
-}
-
-fn arabic_numeral_to_float(s: &str) -> f64 {
-    s.chars().fold(0.0, |acc, c| {
-        acc * 10.0
-            + match c {
-                '٠' => 0.0,
-                '١' => 1.0,
-                '٢' => 2.0,
-                '٣' => 3.0,
-                '٤' => 4.0,
-                '٥' => 5.0,
-                '٦' => 6.0,
-                '٧' => 7.0,
-                '٨' => 8.0,
-                '٩' => 9.0,
-                ',' => return acc,
-                _ => acc,
-            }
-    })
+            identifier.push(self.advance().unwrap());
+        }
+
+        match KEYWORDS.iter().find(|(keyword, _)| *keyword == identifier) {
+            Some((keyword, token)) => (token.clone(), Some(*keyword)),
+            None => (Token::Identifier(identifier), None),
+        }
+    }
+}
+
+fn arabic_numeral_to_float(s: &str) -> Result<f64, String> {
+    match s.split_once('٫') {
+        Some((integer, fraction)) => {
+            let fraction_digits = fraction.chars().count() as i32;
+            Ok(arabic_digits_to_float(integer)?
+                + arabic_digits_to_float(fraction)? / 10f64.powi(fraction_digits))
+        }
+        None => arabic_digits_to_float(s),
+    }
+}
+
+fn arabic_numeral_to_integer(s: &str) -> Option<i64> {
+    s.chars()
+        .filter(|c| !is_grouping_separator(*c))
+        .try_fold(0i64, |acc, c| {
+            let digit = digit_value(c)? as i64;
+            acc.checked_mul(10)?.checked_add(digit)
+        })
+}
+
+fn arabic_digits_to_float(s: &str) -> Result<f64, String> {
+    s.chars()
+        .filter(|c| !is_grouping_separator(*c))
+        .try_fold(0.0, |acc, c| match digit_value(c) {
+            Some(digit) => Ok(acc * 10.0 + digit as f64),
+            None => Err(format!("Invalid character '{}' in number", c)),
+        })
+}
+
+fn is_identifier_start(c: char) -> bool {
+    matches!(c, 'ا'..='ي' | 'آ' | 'أ' | 'إ')
+}
+
+// Digits and underscores may follow the first letter
+fn is_identifier_char(c: char) -> bool {
+    is_identifier_start(c) || matches!(c, 'ة' | 'ى' | '_') || digit_value(c).is_some()
+}
+
+fn is_grouping_separator(c: char) -> bool {
+    c == ',' || c == '٬'
+}
+
+fn digit_value(c: char) -> Option<u32> {
+    match c {
+        '٠'..='٩' => Some(c as u32 - '٠' as u32),
+        '0'..='9' => Some(c as u32 - '0' as u32),
+        _ => None,
+    }
+}
+
+pub fn run(input: &str) -> Result<Vec<SpannedToken>, String> {
+    tokenize(Lexer::new(input, true))
 }
 
-pub fn run(input: &str) -> Vec<Token> {
-    let mut lexer = Lexer::new(input);
+// Lexes a lone value such as a number, where no statement terminators should appear
+pub(crate) fn run_literal(input: &str) -> Result<Vec<SpannedToken>, String> {
+    tokenize(Lexer::new(input, false))
+}
 
+fn tokenize(mut lexer: Lexer) -> Result<Vec<SpannedToken>, String> {
     let mut res = vec![];
-    while let Some(token) = lexer.next_token() {
+    while let Some(token) = lexer.next_token()? {
         res.push(token)
     }
-    return res;
+    Ok(res)
+}
+
+#[cfg(feature = "serde")]
+pub fn tokens_to_json(tokens: &[SpannedToken]) -> String {
+    serde_json::to_string_pretty(tokens).expect("Token serialization cannot fail")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(source: &str) -> Vec<Token> {
+        run(source)
+            .unwrap()
+            .into_iter()
+            .map(|spanned| spanned.token)
+            .collect()
+    }
+
+    fn name(name: &str) -> Token {
+        Token::Identifier(name.to_string())
+    }
+
+    #[test]
+    fn adjacent_dot_before_a_name_is_field_access() {
+        assert_eq!(
+            tokens("سجل.قيمة[٢]"),
+            [
+                name("سجل"),
+                Token::Member,
+                name("قيمة"),
+                Token::LeftBracket,
+                Token::Integer(2),
+                Token::RightBracket,
+                Token::Dot,
+            ]
+        );
+    }
+
+    #[test]
+    fn dot_before_a_keyword_ends_the_statement() {
+        assert_eq!(
+            tokens("متغير س = ص.اطبع س."),
+            [
+                Token::VariableKeyword,
+                name("س"),
+                Token::Equals,
+                name("ص"),
+                Token::Dot,
+                Token::PrintKeyword,
+                name("س"),
+                Token::Dot,
+            ]
+        );
+        assert_eq!(tokens("ص. ع")[1..3], [Token::Dot, name("ع")]);
+    }
+
+    #[test]
+    fn angle_brackets_lex_as_less_and_greater_than() {
+        assert_eq!(tokens("<"), [Token::LT]);
+        assert_eq!(tokens(">"), [Token::GT]);
+        assert_eq!(
+            tokens("٥ > ٣"),
+            [Token::Integer(5), Token::GT, Token::Integer(3), Token::Dot]
+        );
+    }
+
+    #[test]
+    fn waw_words_lex_as_whole_words() {
+        assert_eq!(tokens("و"), [Token::And]);
+        assert_eq!(tokens("أو"), [Token::Or]);
+        assert_eq!(tokens("وإلا"), [Token::ElseKeyword]);
+        assert_eq!(tokens("وزن"), [name("وزن"), Token::Dot]);
+        assert_eq!(
+            tokens("وزن و وإلا"),
+            [name("وزن"), Token::And, Token::ElseKeyword]
+        );
+    }
+
+    #[test]
+    fn two_character_comparisons() {
+        assert_eq!(
+            tokens("<= >= == != ="),
+            [Token::LTE, Token::GTE, Token::EQ, Token::NEQ, Token::Equals]
+        );
+        assert_eq!(
+            tokens("س = ١"),
+            [name("س"), Token::Equals, Token::Integer(1), Token::Dot]
+        );
+    }
+
+    #[test]
+    fn lone_bang_is_an_error() {
+        assert_eq!(
+            run("س ! ١"),
+            Err("Unexpected character '!' at line 1, col 3, did you mean '!='?".to_string())
+        );
+    }
+
+    #[test]
+    fn spans_count_characters_across_lines() {
+        let spans: Vec<(usize, usize)> = run("متغير س = ١.\nاطبع \"مرحبا\".")
+            .unwrap()
+            .into_iter()
+            .map(|spanned| (spanned.span.line, spanned.span.col))
+            .collect();
+        assert_eq!(
+            spans,
+            [
+                (1, 1),
+                (1, 7),
+                (1, 9),
+                (1, 11),
+                (1, 12),
+                (2, 1),
+                (2, 6),
+                (2, 13)
+            ]
+        );
+    }
+
+    #[test]
+    fn unrecognized_character_is_an_error() {
+        assert_eq!(
+            run("متغير س = ١.\nس @ ٢."),
+            Err("Unrecognized character '@' at line 2, col 3".to_string())
+        );
+    }
+
+    #[test]
+    fn decimal_separator() {
+        assert_eq!(tokens("٠٫٥")[0], Token::Number(0.5));
+        assert_eq!(tokens("١٢٫٣٤")[0], Token::Number(12.34));
+        assert_eq!(
+            run("١٫٢٫٣"),
+            Err("Number has more than one decimal separator at line 1, col 1".to_string())
+        );
+        assert_eq!(
+            run("١٫"),
+            Err("Number ends with a decimal separator at line 1, col 1".to_string())
+        );
+    }
+
+    #[test]
+    fn comments_are_skipped() {
+        assert_eq!(
+            tokens("# تعليق\nاطبع ١. # بعد الجملة\n"),
+            [Token::PrintKeyword, Token::Integer(1), Token::Dot]
+        );
+        assert_eq!(
+            tokens("\"# ليس تعليقا\""),
+            [Token::String("# ليس تعليقا".to_string()), Token::Dot]
+        );
+    }
+
+    #[test]
+    fn string_escapes() {
+        assert_eq!(
+            tokens(r#""سطر\nجديد""#)[0],
+            Token::String("سطر\nجديد".to_string())
+        );
+        assert_eq!(
+            tokens(r#""أ\tب\\ج\"د\qه""#)[0],
+            Token::String("أ\tب\\ج\"د\\qه".to_string())
+        );
+    }
+
+    #[test]
+    fn unterminated_string_is_an_error() {
+        assert_eq!(
+            run("متغير س = \"abc"),
+            Err("Unterminated string starting at line 1, col 11".to_string())
+        );
+    }
+
+    #[test]
+    fn western_eastern_and_mixed_digits() {
+        assert_eq!(tokens("123")[0], Token::Integer(123));
+        assert_eq!(tokens("٤٥٦")[0], Token::Integer(456));
+        assert_eq!(tokens("1٢3")[0], Token::Integer(123));
+    }
+
+    #[test]
+    fn large_input_lexes_every_statement() {
+        let source = "متغير قيمة_١ = \"نص\" + ١٢٣. # تعليق\n".repeat(10_000);
+        let spanned = run(&source).unwrap();
+        assert_eq!(spanned.len(), 10_000 * 7);
+        let statement = [
+            Token::VariableKeyword,
+            name("قيمة_١"),
+            Token::Equals,
+            Token::String("نص".to_string()),
+            Token::Plus,
+            Token::Integer(123),
+            Token::Dot,
+        ];
+        for chunk in spanned.chunks(7) {
+            let chunk: Vec<Token> = chunk.iter().map(|t| t.token.clone()).collect();
+            assert_eq!(chunk, statement);
+        }
+        let last = spanned.last().unwrap().span;
+        assert_eq!((last.line, last.col), (10_000, 26));
+    }
+
+    #[test]
+    fn identifiers_continue_with_digits_and_underscores() {
+        assert_eq!(tokens("س١")[0], name("س١"));
+        assert_eq!(tokens("قيمة_أولى")[0], name("قيمة_أولى"));
+        assert_eq!(tokens("متغير")[0], Token::VariableKeyword);
+        assert_eq!(tokens("متغيرات")[0], name("متغيرات"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn tokens_serialize_to_json() {
+        let json: serde_json::Value =
+            serde_json::from_str(&tokens_to_json(&run("متغير س = ٥.").unwrap())).unwrap();
+        let span = |col| serde_json::json!({ "line": 1, "col": col });
+        assert_eq!(
+            json,
+            serde_json::json!([
+                { "token": { "type": "VariableKeyword" }, "span": span(1) },
+                { "token": { "type": "Identifier", "value": "س" }, "span": span(7) },
+                { "token": { "type": "Equals" }, "span": span(9) },
+                { "token": { "type": "Integer", "value": 5 }, "span": span(11) },
+                { "token": { "type": "Dot" }, "span": span(12) },
+            ])
+        );
+    }
+
+    #[test]
+    fn arabic_comma_separates_elements() {
+        assert_eq!(
+            tokens("[١، ٢،٣]"),
+            [
+                Token::LeftBracket,
+                Token::Integer(1),
+                Token::Comma,
+                Token::Integer(2),
+                Token::Comma,
+                Token::Integer(3),
+                Token::RightBracket,
+                Token::Dot,
+            ]
+        );
+    }
+
+    #[test]
+    fn grouping_separator_versus_element_separator() {
+        assert_eq!(tokens("١٬٠٠٠")[0], Token::Integer(1000));
+        assert_eq!(tokens("1,000,000")[0], Token::Integer(1_000_000));
+        assert_eq!(
+            tokens("[١، ٢]")[..4],
+            [
+                Token::LeftBracket,
+                Token::Integer(1),
+                Token::Comma,
+                Token::Integer(2),
+            ]
+        );
+    }
+
+    #[test]
+    fn dangling_grouping_separator_is_an_error() {
+        assert_eq!(
+            run("[١٬]"),
+            Err("Grouping separator must be followed by a digit at line 1, col 2".to_string())
+        );
+        assert_eq!(
+            run("١٬"),
+            Err("Grouping separator must be followed by a digit at line 1, col 1".to_string())
+        );
+    }
+
+    #[test]
+    fn colon_lexes_as_its_own_token() {
+        assert_eq!(tokens("لو س:"), [Token::IfKeyword, name("س"), Token::Colon]);
+    }
+
+    #[test]
+    fn numeral_conversion_rejects_non_digits() {
+        assert_eq!(arabic_numeral_to_float("١٢٫٥"), Ok(12.5));
+        assert_eq!(arabic_numeral_to_float("1٬٠00"), Ok(1000.0));
+        assert_eq!(
+            arabic_numeral_to_float("١أ"),
+            Err("Invalid character 'أ' in number".to_string())
+        );
+        assert_eq!(
+            arabic_numeral_to_float("١٫٢٫٣"),
+            Err("Invalid character '٫' in number".to_string())
+        );
+    }
+
+    #[test]
+    fn line_end_closes_a_statement_unless_it_continues() {
+        assert_eq!(
+            tokens("س\nص"),
+            [name("س"), Token::Dot, name("ص"), Token::Dot]
+        );
+        assert_eq!(
+            tokens("س.\nص."),
+            [name("س"), Token::Dot, name("ص"), Token::Dot]
+        );
+        assert_eq!(
+            tokens("س +\nص"),
+            [name("س"), Token::Plus, name("ص"), Token::Dot]
+        );
+        assert_eq!(
+            tokens("[س،\nص]"),
+            [
+                Token::LeftBracket,
+                name("س"),
+                Token::Comma,
+                name("ص"),
+                Token::RightBracket,
+                Token::Dot,
+            ]
+        );
+    }
+
+    #[test]
+    fn line_end_dot_sits_after_the_last_token() {
+        let spans: Vec<(usize, usize)> = run("س = ١   # تعليق\nص")
+            .unwrap()
+            .into_iter()
+            .filter(|spanned| spanned.token == Token::Dot)
+            .map(|spanned| (spanned.span.line, spanned.span.col))
+            .collect();
+        assert_eq!(spans, [(1, 6), (2, 2)]);
+    }
+
+    #[test]
+    fn hex_and_binary_literals() {
+        assert_eq!(tokens("٠خff")[0], Token::Integer(255));
+        assert_eq!(tokens("0x1A")[0], Token::Integer(26));
+        assert_eq!(tokens("٠ث١٠١")[0], Token::Integer(5));
+        assert_eq!(tokens("0b11")[0], Token::Integer(3));
+    }
+
+    #[test]
+    fn radix_literals_reject_bad_digits() {
+        assert_eq!(
+            run("٠ث١٢"),
+            Err("Invalid digit '٢' in base-2 literal at line 1, col 1".to_string())
+        );
+        assert_eq!(
+            run("0xfg"),
+            Err("Invalid digit 'g' in base-16 literal at line 1, col 1".to_string())
+        );
+        assert_eq!(
+            run("0x."),
+            Err("Missing digits after base-16 prefix at line 1, col 1".to_string())
+        );
+    }
 }
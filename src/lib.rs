@@ -0,0 +1,39 @@
+mod analysis;
+mod builtins;
+mod interpreter;
+mod lexer;
+mod parser;
+
+pub use analysis::check;
+pub use interpreter::{Function, Interpreter, MapKey, Value};
+pub use lexer::run as tokenize;
+pub use lexer::{Span, SpannedToken, Token};
+#[cfg(feature = "serde")]
+pub use parser::ast_to_json;
+pub use parser::run as parse;
+pub use parser::{ASTNode, Operator};
+
+pub fn parse_source(input: &str) -> Result<Vec<ASTNode>, Vec<String>> {
+    let tokens = lexer::run(input).map_err(|e| vec![e])?;
+    parser::run(tokens)
+}
+
+// How many blocks `input` leaves waiting for their تم; input that fails to lex leaves none
+pub fn open_blocks(input: &str) -> usize {
+    lexer::run(input).map_or(0, parser::open_blocks)
+}
+
+#[cfg(feature = "serde")]
+pub fn lex_to_json(input: &str) -> Result<String, String> {
+    Ok(lexer::tokens_to_json(&lexer::run(input)?))
+}
+
+pub fn eval(ast: &[ASTNode]) -> Result<Value, String> {
+    Interpreter::new().interpret(ast)
+}
+
+pub fn run_source(input: &str) -> Result<Interpreter, String> {
+    let mut interpreter = Interpreter::new();
+    interpreter.eval(input)?;
+    Ok(interpreter)
+}
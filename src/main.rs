@@ -1,15 +1,286 @@
-use std::fs;
+use std::io::{self, Write};
+use std::{env, fs, process};
 
-mod interpreter;
-mod lexer;
-mod parser;
+use amoud::{ASTNode, Interpreter, Value};
+
+#[derive(Default)]
+enum Mode {
+    #[default]
+    Run,
+    Tokens,
+    Ast,
+}
+
+#[derive(Default)]
+struct Args {
+    path: Option<String>,
+    mode: Mode,
+    debug: bool,
+    precision: Option<usize>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    args.next(); // Skip the program name
+    let mut parsed = Args::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--tokens" => parsed.mode = Mode::Tokens,
+            "--ast" => parsed.mode = Mode::Ast,
+            "--debug" => parsed.debug = true,
+            "--precision" => {
+                let digits = args
+                    .next()
+                    .ok_or("--precision requires a number of digits")?;
+                parsed.precision = Some(parse_precision(&digits)?);
+            }
+            _ if parsed.path.is_none() => parsed.path = Some(arg),
+            _ => {}
+        }
+    }
+    Ok(parsed)
+}
+
+// Accepts the digits in either script, like number literals do: `--precision ٢` or `--precision 2`
+fn parse_precision(digits: &str) -> Result<usize, String> {
+    let invalid = || format!("--precision expects a number of digits, got '{}'", digits);
+    if digits.is_empty() {
+        return Err(invalid());
+    }
+    digits.chars().try_fold(0usize, |acc, c| {
+        let digit = match c {
+            '٠'..='٩' => c as usize - '٠' as usize,
+            _ => c.to_digit(10).ok_or_else(invalid)? as usize,
+        };
+        acc.checked_mul(10)
+            .and_then(|acc| acc.checked_add(digit))
+            .ok_or_else(invalid)
+    })
+}
+
+fn new_interpreter(precision: Option<usize>) -> Interpreter {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_float_precision(precision);
+    interpreter
+}
+
+fn read_file(path: &str) -> String {
+    fs::read_to_string(path).unwrap_or_else(|e| {
+        eprintln!("Error: could not read '{}': {}", path, e);
+        process::exit(1);
+    })
+}
+
+#[cfg(feature = "serde")]
+fn dump_tokens(path: &str) {
+    match amoud::lex_to_json(&read_file(path)) {
+        Ok(json) => println!("{}", json),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(not(feature = "serde"))]
+fn dump_tokens(_: &str) {
+    eprintln!("Error: --tokens requires building with the serde feature");
+    process::exit(1);
+}
+
+fn parse_file(path: &str) -> Vec<ASTNode> {
+    amoud::parse_source(&read_file(path)).unwrap_or_else(|errors| {
+        for e in errors {
+            eprintln!("Error: {}", e);
+        }
+        process::exit(1);
+    })
+}
+
+fn dump_ast(path: &str) {
+    println!("{:#?}", parse_file(path));
+}
+
+fn run_file(path: &str, debug: bool, precision: Option<usize>) {
+    let ast = parse_file(path);
+    for warning in amoud::check(&ast) {
+        eprintln!("Warning: {}", warning);
+    }
+    let mut interpreter = new_interpreter(precision);
+    match interpreter.interpret(&ast) {
+        Ok(_) => {
+            let summary = if debug {
+                format!(
+                    "Interpretation successful.\nVariables: {:#?}\n",
+                    interpreter.variables()
+                )
+            } else {
+                interpreter.snapshot()
+            };
+            if let Err(e) = interpreter.output().write_all(summary.as_bytes()) {
+                eprintln!("Error: could not write output: {}", e);
+                process::exit(1);
+            }
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+// Lines are gathered until every block they open is closed with تم, then run together
+fn run_repl(precision: Option<usize>) {
+    let mut interpreter = new_interpreter(precision);
+    let mut source = String::new();
+    loop {
+        let prompt = if source.is_empty() {
+            "عمود> "
+        } else {
+            "...   "
+        };
+        print!("{}", prompt);
+        io::stdout().flush().unwrap();
+        // Stdin is not locked across iterations, so اقرأ can read the lines that follow
+        let mut line = String::new();
+        let done = matches!(io::stdin().read_line(&mut line), Ok(0) | Err(_));
+        source.push_str(line.trim_end_matches(['\n', '\r']));
+        source.push('\n');
+        if !done && amoud::open_blocks(&source) > 0 {
+            continue;
+        }
+        run_repl_input(&mut interpreter, &source);
+        source.clear();
+        if done {
+            break;
+        }
+    }
+    println!();
+}
+
+// Unlike a file run, the REPL echoes the value of a last statement that is a bare expression
+fn run_repl_input(interpreter: &mut Interpreter, source: &str) {
+    let ast = match amoud::parse_source(source) {
+        Ok(ast) => ast,
+        Err(errors) => {
+            for e in errors {
+                eprintln!("Error: {}", e);
+            }
+            return;
+        }
+    };
+    match interpreter.interpret(&ast) {
+        Ok(Value::Null) => {}
+        Ok(value) if ast.last().is_some_and(is_expression_statement) => {
+            println!("{}", interpreter.format_value(&value))
+        }
+        Ok(_) => {}
+        Err(e) => eprintln!("Error: {}", e),
+    }
+}
+
+fn is_expression_statement(node: &ASTNode) -> bool {
+    matches!(
+        node,
+        ASTNode::Integer(_)
+            | ASTNode::Number(_)
+            | ASTNode::StringLiteral(_)
+            | ASTNode::BooleanLiteral(_)
+            | ASTNode::NullLiteral
+            | ASTNode::ArrayLiteral(_)
+            | ASTNode::RecordLiteral(_)
+            | ASTNode::MapLiteral(_)
+            | ASTNode::Variable { .. }
+            | ASTNode::BinaryOp { .. }
+            | ASTNode::UnaryOp { .. }
+            | ASTNode::Call { .. }
+            | ASTNode::Index { .. }
+            | ASTNode::FieldAccess { .. }
+            | ASTNode::ConditionalExpr { .. }
+    )
+}
 
 fn main() {
-    // let input = "متغير س = ٥ + ١٠.";
-    let input = fs::read_to_string("./تجربة.عمود").unwrap();
-    let tokens = lexer::run(&input);
-    println!("{:#?}", tokens);
-    let ast = parser::run(tokens);
-    // println!("{:#?}", ast);
-    interpreter::run(ast);
+    let args = parse_args(env::args()).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    });
+    match (args.path, args.mode) {
+        (Some(path), Mode::Run) => run_file(&path, args.debug, args.precision),
+        (Some(path), Mode::Tokens) => dump_tokens(&path),
+        (Some(path), Mode::Ast) => dump_ast(&path),
+        (None, Mode::Run) => run_repl(args.precision),
+        (None, _) => {
+            eprintln!("Error: --tokens and --ast require a source file");
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Args {
+        parse_args(list.iter().map(|arg| arg.to_string())).unwrap()
+    }
+
+    #[test]
+    fn first_argument_is_the_source_path() {
+        let parsed = args(&["amoud", "برنامج.عمود"]);
+        assert_eq!(parsed.path.as_deref(), Some("برنامج.عمود"));
+        assert!(matches!(parsed.mode, Mode::Run));
+        assert_eq!(args(&["amoud"]).path, None);
+    }
+
+    #[test]
+    fn tokens_flag_selects_the_token_dump() {
+        let parsed = args(&["amoud", "--tokens", "برنامج.عمود"]);
+        assert!(matches!(parsed.mode, Mode::Tokens));
+        assert_eq!(parsed.path.as_deref(), Some("برنامج.عمود"));
+        assert!(matches!(
+            args(&["amoud", "برنامج.عمود", "--tokens"]).mode,
+            Mode::Tokens
+        ));
+    }
+
+    #[test]
+    fn ast_flag_selects_the_ast_dump() {
+        let parsed = args(&["amoud", "--ast", "برنامج.عمود"]);
+        assert!(matches!(parsed.mode, Mode::Ast));
+        assert_eq!(parsed.path.as_deref(), Some("برنامج.عمود"));
+        assert!(matches!(args(&["amoud", "--ast"]).mode, Mode::Ast));
+        assert_eq!(args(&["amoud", "--ast"]).path, None);
+    }
+
+    #[test]
+    fn precision_flag_takes_digits_in_either_script() {
+        assert_eq!(args(&["amoud"]).precision, None);
+        assert_eq!(args(&["amoud", "--precision", "٢"]).precision, Some(2));
+        assert_eq!(args(&["amoud", "--precision", "12"]).precision, Some(12));
+        let parsed = args(&["amoud", "--precision", "3", "برنامج.عمود"]);
+        assert_eq!(parsed.precision, Some(3));
+        assert_eq!(parsed.path.as_deref(), Some("برنامج.عمود"));
+    }
+
+    #[test]
+    fn precision_flag_rejects_a_bad_value() {
+        let parse = |list: &[&str]| parse_args(list.iter().map(|arg| arg.to_string())).err();
+        assert_eq!(
+            parse(&["amoud", "--precision", "-1"]),
+            Some("--precision expects a number of digits, got '-1'".to_string())
+        );
+        assert_eq!(
+            parse(&["amoud", "--precision"]),
+            Some("--precision requires a number of digits".to_string())
+        );
+    }
+
+    #[test]
+    fn debug_flag_is_off_unless_given() {
+        assert!(!args(&["amoud", "برنامج.عمود"]).debug);
+        let parsed = args(&["amoud", "--debug", "برنامج.عمود"]);
+        assert!(parsed.debug);
+        assert!(matches!(parsed.mode, Mode::Run));
+        assert_eq!(parsed.path.as_deref(), Some("برنامج.عمود"));
+    }
 }
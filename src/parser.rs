@@ -1,16 +1,23 @@
+use std::fmt;
 use std::iter::Peekable;
+use std::mem;
 
-use crate::lexer::Token;
+use crate::lexer::{Span, SpannedToken, Token};
 
-#[derive(Debug, PartialEq)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Operator {
     Plus,
     Minus,
     Multiply,
     Divide,
+    Modulo,
+    Power,
 
     And,
     Or,
+    Not,
 
     EQ,
     NEQ,
@@ -20,53 +27,247 @@ pub enum Operator {
     LTE,
 }
 
-#[derive(Debug)]
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbol = match self {
+            Operator::Plus => "+",
+            Operator::Minus => "-",
+            Operator::Multiply => "*",
+            Operator::Divide => "/",
+            Operator::Modulo => "٪",
+            Operator::Power => "^",
+            Operator::And => "و",
+            Operator::Or => "أو",
+            Operator::Not => "ليس",
+            Operator::EQ => "==",
+            Operator::NEQ => "!=",
+            Operator::GT => ">",
+            Operator::GTE => ">=",
+            Operator::LT => "<",
+            Operator::LTE => "<=",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "value"))]
 pub enum ASTNode {
+    Integer(i64),
     Number(f64),
     StringLiteral(String),
     BooleanLiteral(bool),
-    Variable(String),
+    NullLiteral,
+    ArrayLiteral(Vec<ASTNode>),
+    RecordLiteral(Vec<(String, ASTNode)>),
+    MapLiteral(Vec<(ASTNode, ASTNode)>),
+    Variable {
+        name: String,
+        span: Span,
+    },
+    Index {
+        target: Box<ASTNode>,
+        index: Box<ASTNode>,
+    },
+    FieldAccess {
+        target: Box<ASTNode>,
+        field: String,
+    },
     IfStatement {
         condition: Box<ASTNode>,
         then_branch: Vec<ASTNode>,
         else_branch: Option<Vec<ASTNode>>,
     },
+    WhileLoop {
+        condition: Box<ASTNode>,
+        body: Vec<ASTNode>,
+    },
+    ConditionalExpr {
+        condition: Box<ASTNode>,
+        then_value: Box<ASTNode>,
+        else_value: Box<ASTNode>,
+    },
+    ForEach {
+        variable: String,
+        iterable: Box<ASTNode>,
+        body: Vec<ASTNode>,
+    },
+    Repeat {
+        count: Box<ASTNode>,
+        body: Vec<ASTNode>,
+    },
     BinaryOp {
         left: Box<ASTNode>,
         operator: Operator,
         right: Box<ASTNode>,
+        span: Span,
+    },
+    UnaryOp {
+        operator: Operator,
+        operand: Box<ASTNode>,
     },
     VariableDeclaration {
         variable: String,
         value: Box<ASTNode>,
     },
+    ConstantDeclaration {
+        variable: String,
+        value: Box<ASTNode>,
+    },
+    Assignment {
+        variable: String,
+        value: Box<ASTNode>,
+    },
+    IndexAssignment {
+        variable: String,
+        index: Box<ASTNode>,
+        value: Box<ASTNode>,
+    },
+    Print {
+        value: Box<ASTNode>,
+        newline: bool,
+    },
+    FunctionDeclaration {
+        name: String,
+        parameters: Vec<String>,
+        body: Vec<ASTNode>,
+    },
+    Call {
+        callee: String,
+        arguments: Vec<ASTNode>,
+        span: Span,
+    },
+    Return {
+        value: Box<ASTNode>,
+        span: Span,
+    },
 }
 
-struct Parser<I: Iterator<Item = Token>> {
+type Binder = fn(String, Box<ASTNode>) -> ASTNode;
+
+struct Parser<I: Iterator<Item = SpannedToken>> {
     tokens: Peekable<I>,
+    span: Span,
+    after_dot: bool,
+    after_else: bool,
+    // How many blocks that close with تم have been entered but not yet closed
+    open_blocks: usize,
+    errors: Vec<String>,
 }
 
-impl<I: Iterator<Item = Token>> Parser<I> {
+impl<I: Iterator<Item = SpannedToken>> Parser<I> {
     fn new(tokens: I) -> Self {
         Parser {
             tokens: tokens.peekable(),
+            span: Span { line: 1, col: 1 },
+            after_dot: false,
+            after_else: false,
+            open_blocks: 0,
+            errors: Vec::new(),
+        }
+    }
+
+    fn peek(&mut self) -> Option<&Token> {
+        self.tokens.peek().map(|t| &t.token)
+    }
+
+    fn peek_span(&mut self) -> Span {
+        self.tokens.peek().map_or(self.span, |t| t.span)
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let SpannedToken { token, span, .. } = self.tokens.next()?;
+        let else_if = self.after_else && span.line == self.span.line;
+        self.span = span;
+        match token {
+            // `وإلا لو` shares the closing تم of the if it continues
+            Token::IfKeyword if !else_if => self.open_blocks += 1,
+            Token::WhileKeyword
+            | Token::ForKeyword
+            | Token::RepeatKeyword
+            | Token::FunctionKeyword => self.open_blocks += 1,
+            Token::EndKeyword => self.open_blocks = self.open_blocks.saturating_sub(1),
+            _ => {}
         }
+        self.after_dot = token == Token::Dot;
+        self.after_else = token == Token::ElseKeyword;
+        Some(token)
     }
 
-    fn parse(&mut self) -> Result<Vec<ASTNode>, String> {
+    fn parse(&mut self) -> Result<Vec<ASTNode>, Vec<String>> {
+        let statements = self.parse_statements(0, &[]);
+        if self.errors.is_empty() {
+            Ok(statements)
+        } else {
+            Err(mem::take(&mut self.errors))
+        }
+    }
+
+    // Parses statements until one of `closers` or the end of input, recording each error and
+    // resuming after it. `depth` is the number of open blocks these statements sit in
+    fn parse_statements(&mut self, depth: usize, closers: &[Token]) -> Vec<ASTNode> {
         let mut statements = Vec::new();
-        while self.tokens.peek().is_some() {
-            statements.push(self.parse_statement()?);
+        while self.peek().is_some_and(|token| !closers.contains(token)) {
+            match self.parse_statement() {
+                Ok(statement) => statements.push(statement),
+                Err(e) => {
+                    self.errors.push(e);
+                    self.synchronize(depth, closers);
+                    // The failed statement swallowed the تم of this block
+                    if self.open_blocks < depth {
+                        break;
+                    }
+                }
+            }
+        }
+        statements
+    }
+
+    // Skips past the end of the failed statement so parsing can resume at the next one. A
+    // statement whose block header failed is skipped up to its تم, so that تم isn't reported
+    // as another error; otherwise parsing resumes after the next dot of the current block, or
+    // at one of its `closers`, such as the وإلا or تم that ends it
+    fn synchronize(&mut self, depth: usize, closers: &[Token]) {
+        if self.open_blocks > depth {
+            while self.open_blocks > depth && self.advance().is_some() {}
+            return;
+        }
+        if self.after_dot || self.open_blocks < depth {
+            return;
+        }
+        loop {
+            let in_block = self.open_blocks == depth;
+            match self.peek() {
+                None => return,
+                Some(token) if in_block && closers.contains(token) => return,
+                _ => {}
+            }
+            if self.advance() == Some(Token::Dot) && self.open_blocks == depth {
+                return;
+            }
         }
-        Ok(statements)
     }
 
     fn parse_statement(&mut self) -> Result<ASTNode, String> {
-        match self.tokens.peek() {
-            Some(Token::VariableKeyword) => self.parse_variable_declaration(),
+        match self.peek() {
+            Some(Token::VariableKeyword) | Some(Token::ConstantKeyword) => {
+                self.parse_variable_declaration()
+            }
             Some(Token::IfKeyword) => self.parse_if_statement(),
+            Some(Token::WhileKeyword) => self.parse_while_loop(),
+            Some(Token::ForKeyword) => self.parse_for_each(),
+            Some(Token::RepeatKeyword) => self.parse_repeat(),
+            Some(Token::PrintKeyword) | Some(Token::PrintLineKeyword) => {
+                self.parse_print_statement()
+            }
+            Some(Token::FunctionKeyword) => self.parse_function_declaration(),
+            Some(Token::ReturnKeyword) => self.parse_return_statement(),
             _ => {
                 let expr = self.parse_expression()?;
+                if self.peek() == Some(&Token::Equals) {
+                    return self.parse_assignment(expr);
+                }
                 self.expect(Token::Dot)?;
                 Ok(expr)
             }
@@ -74,66 +275,191 @@ impl<I: Iterator<Item = Token>> Parser<I> {
     }
 
     fn parse_variable_declaration(&mut self) -> Result<ASTNode, String> {
-        self.tokens.next();
-        if let Some(Token::Identifier(var_name)) = self.tokens.next() {
-            self.expect(Token::Equals)?;
-            let value = self.parse_expression()?;
-            self.expect(Token::Dot)?;
-            Ok(ASTNode::VariableDeclaration {
+        let constant = self.peek() == Some(&Token::ConstantKeyword);
+        let default = if constant { "ثابت" } else { "متغير" };
+        let keyword = self
+            .tokens
+            .peek()
+            .and_then(|t| t.keyword)
+            .unwrap_or(default);
+        self.advance();
+        let var_name = self.expect_identifier(&format!("identifier after '{}'", keyword))?;
+        // Variables declared without a value start out as null
+        if !constant && self.peek() == Some(&Token::Dot) {
+            self.advance();
+            return Ok(ASTNode::VariableDeclaration {
                 variable: var_name,
-                value: Box::new(value),
-            })
+                value: Box::new(ASTNode::NullLiteral),
+            });
+        }
+        self.expect(Token::Equals)?;
+        let bind: Binder = if constant {
+            |variable, value| ASTNode::ConstantDeclaration { variable, value }
         } else {
-            Err("Expected identifier after 'متغير'".to_string())
+            |variable, value| ASTNode::VariableDeclaration { variable, value }
+        };
+        let value = Box::new(self.parse_value(bind)?);
+        Ok(bind(var_name, value))
+    }
+
+    fn parse_assignment(&mut self, target: ASTNode) -> Result<ASTNode, String> {
+        self.advance();
+        let bind: Binder = |variable, value| ASTNode::Assignment { variable, value };
+        match target {
+            ASTNode::Variable { name, .. } => {
+                let value = Box::new(self.parse_value(bind)?);
+                Ok(bind(name, value))
+            }
+            // Only a variable's own elements can be assigned, as in `م["مفتاح"] = ١.`
+            ASTNode::Index { target, index } => match *target {
+                ASTNode::Variable { name, .. } => Ok(ASTNode::IndexAssignment {
+                    variable: name,
+                    index,
+                    value: Box::new(self.parse_value(bind)?),
+                }),
+                _ => Err(format!("Invalid assignment target at {}", self.span)),
+            },
+            _ => Err(format!("Invalid assignment target at {}", self.span)),
+        }
+    }
+
+    // In a chain like `أ = ب = ٥.` every target is bound with the same `bind` as the
+    // first, so a declaration declares them all and an assignment needs them all declared;
+    // the interpreter checks each target before assigning, so a bad one changes nothing
+    fn parse_value(&mut self, bind: Binder) -> Result<ASTNode, String> {
+        if self.peek() == Some(&Token::IfKeyword) {
+            return self.parse_if_statement();
         }
+        let value = self.parse_expression()?;
+        if self.peek() == Some(&Token::Equals) {
+            let name = match value {
+                ASTNode::Variable { name, .. } => name,
+                _ => return Err(format!("Invalid assignment target at {}", self.peek_span())),
+            };
+            self.advance();
+            let value = Box::new(self.parse_value(bind)?);
+            return Ok(bind(name, value));
+        }
+        self.expect(Token::Dot)?;
+        Ok(value)
+    }
+
+    fn parse_print_statement(&mut self) -> Result<ASTNode, String> {
+        let newline = self.advance() == Some(Token::PrintLineKeyword);
+        let value = self.parse_expression()?;
+        self.expect(Token::Dot)?;
+        Ok(ASTNode::Print {
+            value: Box::new(value),
+            newline,
+        })
     }
 
+    // Precedence, loosest first: conditional, logical, comparison, additive,
+    // multiplicative, unary, power, indexing and field access, primary
     fn parse_expression(&mut self) -> Result<ASTNode, String> {
-        self.parse_comparison()
+        if self.peek() == Some(&Token::ConditionalKeyword) {
+            return self.parse_conditional();
+        }
+        self.parse_logical()
     }
 
-    fn parse_comparison(&mut self) -> Result<ASTNode, String> {
-        let mut expr = self.parse_additive()?;
+    fn parse_conditional(&mut self) -> Result<ASTNode, String> {
+        self.advance();
+        let condition = self.parse_expression()?;
+        self.expect(Token::ConditionalThenKeyword)?;
+        let then_value = self.parse_expression()?;
+        self.expect(Token::ElseKeyword)?;
+        let else_value = self.parse_expression()?;
+
+        Ok(ASTNode::ConditionalExpr {
+            condition: Box::new(condition),
+            then_value: Box::new(then_value),
+            else_value: Box::new(else_value),
+        })
+    }
+
+    fn parse_logical(&mut self) -> Result<ASTNode, String> {
+        let mut expr = self.parse_comparison()?;
 
         loop {
-            let op = match self.tokens.peek() {
-                Some(Token::LT) => Operator::LT,
-                Some(Token::GT) => Operator::GT,
-                Some(Token::LTE) => Operator::LTE,
-                Some(Token::GTE) => Operator::GTE,
-                Some(Token::EQ) => Operator::EQ,
-                Some(Token::NEQ) => Operator::NEQ,
+            let op = match self.peek() {
+                Some(Token::And) => Operator::And,
+                Some(Token::Or) => Operator::Or,
                 _ => break,
             };
 
-            self.tokens.next(); // Consume the operator
-            let right = self.parse_additive()?;
+            self.advance(); // Consume the operator
+            let span = self.span;
+            let right = self.parse_comparison()?;
             expr = ASTNode::BinaryOp {
                 left: Box::new(expr),
                 operator: op,
                 right: Box::new(right),
+                span,
             };
         }
 
         Ok(expr)
     }
 
+    // Comparisons don't chain: `١ < ٢ < ٣` is rejected rather than comparing a boolean
+    // with a number, so such conditions must be spelled out with `و`
+    fn parse_comparison(&mut self) -> Result<ASTNode, String> {
+        let left = self.parse_additive()?;
+
+        let op = match self.comparison_operator() {
+            Some(op) => op,
+            None => return Ok(left),
+        };
+        self.advance(); // Consume the operator
+        let span = self.span;
+        let right = self.parse_additive()?;
+
+        if self.comparison_operator().is_some() {
+            return Err(format!(
+                "Comparisons cannot be chained at {}, combine them with 'و' instead",
+                self.peek_span()
+            ));
+        }
+
+        Ok(ASTNode::BinaryOp {
+            left: Box::new(left),
+            operator: op,
+            right: Box::new(right),
+            span,
+        })
+    }
+
+    fn comparison_operator(&mut self) -> Option<Operator> {
+        match self.peek() {
+            Some(Token::LT) => Some(Operator::LT),
+            Some(Token::GT) => Some(Operator::GT),
+            Some(Token::LTE) => Some(Operator::LTE),
+            Some(Token::GTE) => Some(Operator::GTE),
+            Some(Token::EQ) => Some(Operator::EQ),
+            Some(Token::NEQ) => Some(Operator::NEQ),
+            _ => None,
+        }
+    }
+
     fn parse_additive(&mut self) -> Result<ASTNode, String> {
         let mut left = self.parse_multiplicative()?;
 
-        while let Some(token) = self.tokens.peek() {
+        while let Some(token) = self.peek() {
             match token {
                 Token::Plus | Token::Minus => {
-                    let op = match self.tokens.next().unwrap() {
+                    let op = match self.advance().unwrap() {
                         Token::Plus => Operator::Plus,
                         Token::Minus => Operator::Minus,
                         _ => unreachable!(),
                     };
+                    let span = self.span;
                     let right = self.parse_multiplicative()?;
                     left = ASTNode::BinaryOp {
                         left: Box::new(left),
                         operator: op,
                         right: Box::new(right),
+                        span,
                     };
                 }
                 _ => break,
@@ -144,21 +470,24 @@ impl<I: Iterator<Item = Token>> Parser<I> {
     }
 
     fn parse_multiplicative(&mut self) -> Result<ASTNode, String> {
-        let mut left = self.parse_primary()?;
+        let mut left = self.parse_unary()?;
 
-        while let Some(token) = self.tokens.peek() {
+        while let Some(token) = self.peek() {
             match token {
-                Token::Multiply | Token::Divide => {
-                    let op = match self.tokens.next().unwrap() {
+                Token::Multiply | Token::Divide | Token::Modulo => {
+                    let op = match self.advance().unwrap() {
                         Token::Multiply => Operator::Multiply,
                         Token::Divide => Operator::Divide,
+                        Token::Modulo => Operator::Modulo,
                         _ => unreachable!(),
                     };
-                    let right = self.parse_primary()?;
+                    let span = self.span;
+                    let right = self.parse_unary()?;
                     left = ASTNode::BinaryOp {
                         left: Box::new(left),
                         operator: op,
                         right: Box::new(right),
+                        span,
                     };
                 }
                 _ => break,
@@ -168,40 +497,159 @@ impl<I: Iterator<Item = Token>> Parser<I> {
         Ok(left)
     }
 
+    fn parse_unary(&mut self) -> Result<ASTNode, String> {
+        let op = match self.peek() {
+            Some(Token::Minus) => Operator::Minus,
+            Some(Token::Not) => Operator::Not,
+            _ => return self.parse_power(),
+        };
+
+        self.advance(); // Consume the operator
+        let operand = self.parse_unary()?;
+        Ok(ASTNode::UnaryOp {
+            operator: op,
+            operand: Box::new(operand),
+        })
+    }
+
+    fn parse_power(&mut self) -> Result<ASTNode, String> {
+        let base = self.parse_index()?;
+        if self.peek() != Some(&Token::Power) {
+            return Ok(base);
+        }
+
+        self.advance(); // Consume the operator
+        let span = self.span;
+        // Right-associative: the exponent may itself be a power (or negated)
+        let exponent = self.parse_unary()?;
+        Ok(ASTNode::BinaryOp {
+            left: Box::new(base),
+            operator: Operator::Power,
+            right: Box::new(exponent),
+            span,
+        })
+    }
+
+    fn parse_index(&mut self) -> Result<ASTNode, String> {
+        let mut expr = self.parse_primary()?;
+        loop {
+            expr = match self.peek() {
+                Some(Token::LeftBracket) => {
+                    self.advance();
+                    let index = self.parse_expression()?;
+                    self.expect(Token::RightBracket)?;
+                    ASTNode::Index {
+                        target: Box::new(expr),
+                        index: Box::new(index),
+                    }
+                }
+                Some(Token::Member) => {
+                    self.advance();
+                    ASTNode::FieldAccess {
+                        target: Box::new(expr),
+                        field: self.expect_identifier("field name")?,
+                    }
+                }
+                _ => return Ok(expr),
+            };
+        }
+    }
+
     fn parse_primary(&mut self) -> Result<ASTNode, String> {
-        match self.tokens.next() {
+        match self.advance() {
+            Some(Token::Integer(n)) => Ok(ASTNode::Integer(n)),
             Some(Token::Number(n)) => Ok(ASTNode::Number(n)),
             Some(Token::String(s)) => Ok(ASTNode::StringLiteral(s)),
             Some(Token::True) => Ok(ASTNode::BooleanLiteral(true)),
             Some(Token::False) => Ok(ASTNode::BooleanLiteral(false)),
-            Some(Token::Identifier(name)) => Ok(ASTNode::Variable(name)),
+            Some(Token::Null) => Ok(ASTNode::NullLiteral),
+            Some(Token::Identifier(name)) => {
+                let span = self.span;
+                if self.peek() == Some(&Token::LeftParen) {
+                    self.parse_call(name, span)
+                } else {
+                    Ok(ASTNode::Variable { name, span })
+                }
+            }
+            Some(Token::RecordKeyword) => self.parse_record(),
+            Some(Token::MapKeyword) => self.parse_map(),
+            Some(Token::LeftBracket) => Ok(ASTNode::ArrayLiteral(
+                self.parse_expression_list(Token::RightBracket)?,
+            )),
             Some(Token::LeftParen) => {
                 let expr = self.parse_expression()?;
                 self.expect(Token::RightParen)?;
                 Ok(expr)
             }
-            _ => Err("Unexpected token".to_string()),
+            _ => Err(format!("Unexpected token at {}", self.span)),
         }
     }
 
-    fn parse_if_statement(&mut self) -> Result<ASTNode, String> {
-        self.tokens.next();
-        let condition = self.parse_expression()?;
-        self.expect(Token::ThenKeyword)?;
+    fn parse_record(&mut self) -> Result<ASTNode, String> {
+        self.expect(Token::LeftParen)?;
+        let mut fields: Vec<(String, ASTNode)> = Vec::new();
+        while self.peek() != Some(&Token::RightParen) {
+            let name = self.expect_identifier("field name")?;
+            if fields.iter().any(|(existing, _)| *existing == name) {
+                return Err(format!("Duplicate field {} at {}", name, self.span));
+            }
+            self.expect(Token::Equals)?;
+            fields.push((name, self.parse_expression()?));
+            if self.peek() != Some(&Token::Comma) {
+                break;
+            }
+            self.advance();
+            if self.peek() == Some(&Token::RightParen) {
+                return Err(format!("Trailing comma before RightParen at {}", self.span));
+            }
+        }
+        self.expect(Token::RightParen)?;
+        Ok(ASTNode::RecordLiteral(fields))
+    }
 
-        let mut then_branch = Vec::new();
-        while self.tokens.peek() != Some(&Token::ElseKeyword) && self.tokens.peek().is_some() {
-            then_branch.push(self.parse_statement()?);
+    fn parse_map(&mut self) -> Result<ASTNode, String> {
+        self.expect(Token::LeftParen)?;
+        let mut entries = Vec::new();
+        while self.peek() != Some(&Token::RightParen) {
+            let key = self.parse_expression()?;
+            self.expect(Token::Colon)?;
+            entries.push((key, self.parse_expression()?));
+            if self.peek() != Some(&Token::Comma) {
+                break;
+            }
+            self.advance();
+            if self.peek() == Some(&Token::RightParen) {
+                return Err(format!("Trailing comma before RightParen at {}", self.span));
+            }
         }
+        self.expect(Token::RightParen)?;
+        Ok(ASTNode::MapLiteral(entries))
+    }
+
+    fn parse_if_statement(&mut self) -> Result<ASTNode, String> {
+        self.advance();
+        let condition = self.parse_condition("لو")?;
+
+        let depth = self.open_blocks;
+        let then_branch = self.parse_statements(depth, &[Token::ElseKeyword, Token::EndKeyword]);
 
-        let else_branch = if self.tokens.peek() == Some(&Token::ElseKeyword) {
-            self.tokens.next();
-            let mut else_statements = Vec::new();
-            while self.tokens.peek().is_some() {
-                else_statements.push(self.parse_statement()?);
+        let else_branch = if self.open_blocks < depth {
+            None
+        } else if self.peek() == Some(&Token::ElseKeyword) {
+            self.advance();
+            if self.peek() == Some(&Token::IfKeyword) && self.peek_span().line == self.span.line {
+                // `وإلا لو` on one line chains into a nested if that forms the whole else
+                // branch and shares its closing `تم`; a لو on the next line starts an
+                // ordinary if inside the else block
+                return Ok(ASTNode::IfStatement {
+                    condition: Box::new(condition),
+                    then_branch,
+                    else_branch: Some(vec![self.parse_if_statement()?]),
+                });
             }
-            Some(else_statements)
+            Some(self.parse_block()?)
         } else {
+            self.expect(Token::EndKeyword)?;
             None
         };
 
@@ -212,19 +660,441 @@ impl<I: Iterator<Item = Token>> Parser<I> {
         })
     }
 
+    // A condition ends at `ف` or, alternatively, at a colon: `لو س > ٠:`
+    fn parse_condition(&mut self, keyword: &str) -> Result<ASTNode, String> {
+        if matches!(
+            self.peek(),
+            Some(Token::ThenKeyword) | Some(Token::Colon) | None
+        ) {
+            let span = self.peek_span();
+            return Err(format!(
+                "الشرط مفقود بعد '{}' عند السطر {}، العمود {}",
+                keyword, span.line, span.col
+            ));
+        }
+        let condition = self.parse_expression()?;
+        self.expect_then(&format!("شرط '{}'", keyword))?;
+        Ok(condition)
+    }
+
+    // Like the `ف` after a condition, the one opening a loop or function body may be a colon
+    fn expect_then(&mut self, after: &str) -> Result<(), String> {
+        if !matches!(self.peek(), Some(Token::ThenKeyword) | Some(Token::Colon)) {
+            let span = self.peek_span();
+            return Err(format!(
+                "متوقع 'ف' أو ':' بعد {} عند السطر {}، العمود {}",
+                after, span.line, span.col
+            ));
+        }
+        self.advance();
+        Ok(())
+    }
+
+    fn parse_while_loop(&mut self) -> Result<ASTNode, String> {
+        self.advance();
+        let condition = self.parse_condition("بينما")?;
+        let body = self.parse_block()?;
+
+        Ok(ASTNode::WhileLoop {
+            condition: Box::new(condition),
+            body,
+        })
+    }
+
+    fn parse_function_declaration(&mut self) -> Result<ASTNode, String> {
+        self.advance();
+        let name = self.expect_identifier("function name after 'دالة'")?;
+
+        self.expect(Token::LeftParen)?;
+        let mut parameters = Vec::new();
+        if self.peek() != Some(&Token::RightParen) {
+            loop {
+                parameters.push(self.expect_identifier("parameter name")?);
+                if self.peek() != Some(&Token::Comma) {
+                    break;
+                }
+                self.advance();
+            }
+        }
+        self.expect(Token::RightParen)?;
+        self.expect_then("رأس الدالة")?;
+        let body = self.parse_block()?;
+
+        Ok(ASTNode::FunctionDeclaration {
+            name,
+            parameters,
+            body,
+        })
+    }
+
+    fn parse_return_statement(&mut self) -> Result<ASTNode, String> {
+        self.advance();
+        let span = self.span;
+        let value = self.parse_expression()?;
+        self.expect(Token::Dot)?;
+        Ok(ASTNode::Return {
+            value: Box::new(value),
+            span,
+        })
+    }
+
+    fn parse_for_each(&mut self) -> Result<ASTNode, String> {
+        self.advance();
+        let variable = self.expect_identifier("loop variable after 'لكل'")?;
+        self.expect(Token::InKeyword)?;
+        let iterable = self.parse_expression()?;
+        self.expect_then("رأس 'لكل'")?;
+        let body = self.parse_block()?;
+
+        Ok(ASTNode::ForEach {
+            variable,
+            iterable: Box::new(iterable),
+            body,
+        })
+    }
+
+    fn parse_repeat(&mut self) -> Result<ASTNode, String> {
+        self.advance();
+        let count = self.parse_expression()?;
+        self.expect(Token::TimesKeyword)?;
+        self.expect_then("'مرة'")?;
+        let body = self.parse_block()?;
+
+        Ok(ASTNode::Repeat {
+            count: Box::new(count),
+            body,
+        })
+    }
+
+    fn parse_call(&mut self, callee: String, span: Span) -> Result<ASTNode, String> {
+        self.advance();
+        let arguments = self.parse_expression_list(Token::RightParen)?;
+        Ok(ASTNode::Call {
+            callee,
+            arguments,
+            span,
+        })
+    }
+
+    fn parse_expression_list(&mut self, closing: Token) -> Result<Vec<ASTNode>, String> {
+        let mut expressions = Vec::new();
+        if self.peek() != Some(&closing) {
+            loop {
+                expressions.push(self.parse_expression()?);
+                if self.peek() != Some(&Token::Comma) {
+                    break;
+                }
+                self.advance();
+                if self.peek() == Some(&closing) {
+                    return Err(format!(
+                        "Trailing comma before {:?} at {}",
+                        closing, self.span
+                    ));
+                }
+            }
+        }
+        self.expect(closing)?;
+        Ok(expressions)
+    }
+
+    fn parse_block(&mut self) -> Result<Vec<ASTNode>, String> {
+        let depth = self.open_blocks;
+        let statements = self.parse_statements(depth, &[Token::EndKeyword]);
+        if self.open_blocks == depth {
+            self.expect(Token::EndKeyword)?;
+        }
+        Ok(statements)
+    }
+
+    fn expect_identifier(&mut self, expected: &str) -> Result<String, String> {
+        let keyword = self.tokens.peek().and_then(|t| t.keyword);
+        match (self.advance(), keyword) {
+            (Some(Token::Identifier(name)), _) => Ok(name),
+            (_, Some(keyword)) => Err(format!(
+                "Expected {} at {}, but '{}' is a reserved keyword",
+                expected, self.span, keyword
+            )),
+            _ => Err(format!("Expected {} at {}", expected, self.span)),
+        }
+    }
+
+    // Leaves a mismatched token in place so error recovery starts from it
     fn expect(&mut self, expected: Token) -> Result<(), String> {
-        if self.tokens.next() == Some(expected.clone()) {
-            Ok(())
-        } else {
-            Err(format!("Expected {:?}", expected))
+        match self.peek() {
+            Some(token) if *token == expected => {
+                self.advance();
+                Ok(())
+            }
+            Some(token) => Err(format!(
+                "Expected {:?}, found {:?} at {}",
+                expected,
+                token.clone(),
+                self.peek_span()
+            )),
+            None => Err(format!(
+                "Expected {:?}, found end of input at {}",
+                expected, self.span
+            )),
         }
     }
 }
 
-pub fn run(tokens: Vec<Token>) -> Vec<ASTNode> {
+pub fn run(tokens: Vec<SpannedToken>) -> Result<Vec<ASTNode>, Vec<String>> {
     let mut parser = Parser::new(tokens.into_iter());
-    match parser.parse() {
-        Ok(ast) => ast,
-        Err(e) => panic!("Error: {}", e),
+    parser.parse()
+}
+
+// How many blocks the tokens open without closing them with تم
+pub fn open_blocks(tokens: Vec<SpannedToken>) -> usize {
+    let mut parser = Parser::new(tokens.into_iter());
+    while parser.advance().is_some() {}
+    parser.open_blocks
+}
+
+#[cfg(feature = "serde")]
+pub fn ast_to_json(ast: &[ASTNode]) -> String {
+    serde_json::to_string_pretty(ast).expect("AST serialization cannot fail")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn errors(source: &str) -> Vec<String> {
+        crate::parse_source(source).unwrap_err()
+    }
+
+    #[test]
+    fn keyword_as_a_name_is_reported_as_written() {
+        assert_eq!(
+            errors("متغير متغير = ٥."),
+            ["Expected identifier after 'متغير' at line 1, col 7, but 'متغير' is a reserved keyword"]
+        );
+        assert_eq!(
+            errors("عرف لو = ٥."),
+            ["Expected identifier after 'عرف' at line 1, col 5, but 'لو' is a reserved keyword"]
+        );
+    }
+
+    #[test]
+    fn error_in_a_block_header_skips_to_its_end() {
+        assert_eq!(
+            errors("لو ٥ ٦ ف\n  اطبع ١.\nتم\nاطبع ٢."),
+            ["متوقع 'ف' أو ':' بعد شرط 'لو' عند السطر 1، العمود 6"]
+        );
+        assert_eq!(
+            errors("بينما نعم ف\n  لو ٥ ٦ ف\n    اطبع ١.\n  تم\n  متغير = ٢.\nتم"),
+            [
+                "متوقع 'ف' أو ':' بعد شرط 'لو' عند السطر 2، العمود 8",
+                "Expected identifier after 'متغير' at line 5, col 9",
+            ]
+        );
+    }
+
+    #[test]
+    fn error_in_a_block_resumes_at_the_next_statement() {
+        assert_eq!(
+            errors("دالة د() ف اطبع (١. اطبع ٢. متغير = ٣. تم"),
+            [
+                "Expected RightParen, found Dot at line 1, col 19",
+                "Expected identifier after 'متغير' at line 1, col 35",
+            ]
+        );
+        assert_eq!(
+            errors("بينما نعم ف\n  لو نعم ف\n    اطبع (١.\n  تم\n  اطبع ).\nتم\nاطبع ٣."),
+            [
+                "Expected RightParen, found Dot at line 3, col 12",
+                "Unexpected token at line 5, col 8",
+            ]
+        );
+        assert_eq!(
+            errors("لو نعم ف\n  اطبع (١\nوإلا\n  متغير = ٢\nتم"),
+            [
+                "Expected RightParen, found ElseKeyword at line 3, col 1",
+                "Expected identifier after 'متغير' at line 4, col 9",
+            ]
+        );
+    }
+
+    #[test]
+    fn independent_errors_are_all_reported() {
+        assert_eq!(
+            errors("لو ٥ ٦ ف\n  اطبع ١.\nتم\nمتغير = ٣.\nاطبع ٢.\nاطبع ).\n"),
+            [
+                "متوقع 'ف' أو ':' بعد شرط 'لو' عند السطر 1، العمود 6",
+                "Expected identifier after 'متغير' at line 4, col 7",
+                "Unexpected token at line 6, col 6",
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_tokens_are_an_error_not_a_panic() {
+        let tokens = [
+            Token::VariableKeyword,
+            Token::Equals,
+            Token::Plus,
+            Token::Dot,
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, token)| SpannedToken {
+            token,
+            span: Span {
+                line: 1,
+                col: i + 1,
+            },
+            keyword: None,
+        })
+        .collect();
+        assert_eq!(
+            run(tokens).unwrap_err(),
+            ["Expected identifier after 'متغير' at line 1, col 2"]
+        );
+    }
+
+    #[test]
+    fn errors_name_the_line_and_column() {
+        assert_eq!(
+            errors("متغير س = ١.\n\nاطبع \"نص\" ٣."),
+            ["Expected Dot, found Integer(3) at line 3, col 11"]
+        );
+    }
+
+    #[test]
+    fn else_if_ladder_nests_in_the_else_branch() {
+        let ast = crate::parse_source("لو أ ف ١. وإلا لو ب ف ٢. وإلا ٣. تم").unwrap();
+        let [ASTNode::IfStatement {
+            else_branch: Some(outer_else),
+            ..
+        }] = ast.as_slice()
+        else {
+            panic!("expected a single if, got {:?}", ast);
+        };
+        let [ASTNode::IfStatement {
+            then_branch,
+            else_branch: Some(inner_else),
+            ..
+        }] = outer_else.as_slice()
+        else {
+            panic!("expected a nested if, got {:?}", outer_else);
+        };
+        assert!(matches!(then_branch.as_slice(), [ASTNode::Integer(2)]));
+        assert!(matches!(inner_else.as_slice(), [ASTNode::Integer(3)]));
+    }
+
+    #[test]
+    fn if_without_then_names_the_missing_keyword() {
+        assert_eq!(
+            errors("لو س > ١\n  اطبع س.\nتم")[0],
+            "متوقع 'ف' أو ':' بعد شرط 'لو' عند السطر 1، العمود 9"
+        );
+    }
+
+    #[test]
+    fn if_without_a_condition_is_reported() {
+        assert_eq!(
+            errors("لو ف\n  اطبع ١.\nتم")[0],
+            "الشرط مفقود بعد 'لو' عند السطر 1، العمود 4"
+        );
+    }
+
+    #[test]
+    fn comparisons_cannot_be_chained() {
+        assert_eq!(
+            errors("١ < ٢ < ٣."),
+            ["Comparisons cannot be chained at line 1, col 7, combine them with 'و' instead"]
+        );
+        assert!(crate::parse_source("١ < ٢ و ٢ < ٣.").is_ok());
+    }
+
+    #[test]
+    fn trailing_commas_are_rejected() {
+        assert_eq!(
+            errors("[١، ٢،]."),
+            ["Trailing comma before RightBracket at line 1, col 6"]
+        );
+        assert_eq!(
+            errors("د(١،)."),
+            ["Trailing comma before RightParen at line 1, col 4"]
+        );
+        assert!(crate::parse_source("[١، ٢، ٣].").is_ok());
+    }
+
+    // Fully parenthesizes an expression so its grouping can be compared as text
+    fn grouping(node: &ASTNode) -> String {
+        match node {
+            ASTNode::BinaryOp {
+                left,
+                operator,
+                right,
+                ..
+            } => format!("({} {} {})", grouping(left), operator, grouping(right)),
+            ASTNode::Integer(n) => n.to_string(),
+            other => panic!("unexpected node {:?}", other),
+        }
+    }
+
+    #[test]
+    fn precedence_runs_from_logical_to_power() {
+        let ast = crate::parse_source("١ + ٢ < ٣ و ٤ > ٥.").unwrap();
+        assert_eq!(grouping(&ast[0]), "(((1 + 2) < 3) و (4 > 5))");
+        let ast = crate::parse_source("١ - ٢ * ٣ ^ ٢ أو ٤ == ٥.").unwrap();
+        assert_eq!(grouping(&ast[0]), "((1 - (2 * (3 ^ 2))) أو (4 == 5))");
+    }
+
+    #[test]
+    fn colon_opens_any_block() {
+        let ast = crate::parse_source("لو أ: ١. تم").unwrap();
+        assert!(matches!(ast.as_slice(), [ASTNode::IfStatement { .. }]));
+        let ast = crate::parse_source("لكل ع في أ: ع. تم").unwrap();
+        assert!(matches!(ast.as_slice(), [ASTNode::ForEach { .. }]));
+        let ast = crate::parse_source("كرر ٣ مرة: ١. تم").unwrap();
+        assert!(matches!(ast.as_slice(), [ASTNode::Repeat { .. }]));
+        let ast = crate::parse_source("دالة د(س): أرجع س. تم").unwrap();
+        assert!(matches!(
+            ast.as_slice(),
+            [ASTNode::FunctionDeclaration { .. }]
+        ));
+    }
+
+    #[test]
+    fn block_without_then_or_colon_is_reported() {
+        assert_eq!(
+            errors("كرر ٣ مرة ١. تم")[0],
+            "متوقع 'ف' أو ':' بعد 'مرة' عند السطر 1، العمود 11"
+        );
+        assert_eq!(
+            errors("دالة د() أرجع ١. تم")[0],
+            "متوقع 'ف' أو ':' بعد رأس الدالة عند السطر 1، العمود 10"
+        );
+    }
+
+    #[test]
+    fn expect_leaves_a_mismatched_token_in_place() {
+        let mut parser = Parser::new(crate::lexer::run("٣ ]").unwrap().into_iter());
+        assert_eq!(
+            parser.expect(Token::RightParen),
+            Err("Expected RightParen, found Integer(3) at line 1, col 1".to_string())
+        );
+        assert_eq!(parser.peek(), Some(&Token::Integer(3)));
+        assert_eq!(parser.expect(Token::Integer(3)), Ok(()));
+        assert_eq!(parser.peek(), Some(&Token::RightBracket));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn declaration_serializes_to_json() {
+        let ast = crate::parse_source("متغير س = ٥.").unwrap();
+        let json: serde_json::Value = serde_json::from_str(&ast_to_json(&ast)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([{
+                "type": "VariableDeclaration",
+                "value": {
+                    "variable": "س",
+                    "value": { "type": "Integer", "value": 5 },
+                },
+            }])
+        );
     }
 }
@@ -0,0 +1,24 @@
+use amoud::{Interpreter, Token, Value};
+
+#[test]
+fn run_source_exposes_the_final_variables() {
+    let interpreter = amoud::run_source("متغير س = ١٧ + ٢٥.").unwrap();
+    assert_eq!(interpreter.get_variable("س"), Some(&Value::Integer(42)));
+    assert_eq!(interpreter.get_variable("ص"), None);
+}
+
+#[test]
+fn interpreter_runs_tokens_parsed_through_the_crate_root() {
+    let tokens = amoud::tokenize("متغير س = [١، ٢].").unwrap();
+    assert_eq!(tokens[0].token, Token::VariableKeyword);
+    let ast = amoud::parse(tokens).unwrap();
+
+    let mut interpreter = Interpreter::new();
+    interpreter.interpret(&ast).unwrap();
+    assert_eq!(
+        interpreter.get_variable("س"),
+        Some(&Value::Array(vec![Value::Integer(1), Value::Integer(2)]))
+    );
+    assert_eq!(interpreter.variables().len(), 1);
+    assert_eq!(interpreter.eval("طول(س)."), Ok(Value::Integer(2)));
+}
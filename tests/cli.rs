@@ -0,0 +1,96 @@
+use std::io::Write;
+use std::process::{self, Command, Output, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{env, fs};
+
+fn run_file(source: &str, args: &[&str]) -> Output {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let path = env::temp_dir().join(format!(
+        "amoud-test-{}-{}.عمود",
+        process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::write(&path, source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_amoud"))
+        .arg(&path)
+        .args(args)
+        .output()
+        .unwrap();
+    fs::remove_file(&path).unwrap();
+    output
+}
+
+fn stdout(output: Output) -> String {
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn repl(input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_amoud"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn repl_read_takes_the_next_input_line() {
+    let output = repl("متغير س = اقرأ().\nمرحبا\nس + \"!\".\n");
+    assert!(output.status.success());
+    assert!(stdout(output).contains("مرحبا!"));
+}
+
+#[test]
+fn file_mode_discards_bare_expression_values() {
+    let output = run_file("متغير س = ٢.\nس + ٤٠.\n", &[]);
+    assert!(output.status.success());
+    assert_eq!(stdout(output), "س = 2\n");
+}
+
+#[test]
+fn repl_prints_bare_expression_values() {
+    let output = repl("متغير س = ٢.\nس = ٣.\nدالة د() ف تم\nلو نعم ف س = ٥. تم\nس + ٣٧.\n");
+    assert!(output.status.success());
+    assert_eq!(stdout(output), "عمود> عمود> عمود> عمود> عمود> 42\nعمود> \n");
+}
+
+#[test]
+fn repl_runs_a_block_once_its_end_is_typed() {
+    let output = repl("دالة ضعف(ن) ف\n  أرجع ن * ٢.\nتم\nضعف(٢١).\n");
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+    assert!(stdout(output).contains("42\n"));
+}
+
+#[test]
+fn print_writes_to_stdout() {
+    let output = run_file("اطبع \"مرحبا\".", &[]);
+    assert!(output.status.success());
+    assert_eq!(stdout(output), "مرحبا");
+}
+
+#[test]
+fn missing_file_fails_with_a_message() {
+    let output = Command::new(env!("CARGO_BIN_EXE_amoud"))
+        .arg("لا_يوجد.عمود")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .starts_with("Error: could not read 'لا_يوجد.عمود'"));
+}
+
+#[test]
+fn precision_flag_applies_to_printed_floats() {
+    let output = run_file("اطبع_سطر ١ / ٣.", &["--precision", "٣"]);
+    assert!(output.status.success());
+    assert_eq!(stdout(output), "0.333\n");
+}